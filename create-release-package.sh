@@ -11,6 +11,8 @@ mkdir -p $OUTPUT_CMD_DIR || exit 1
 
 cp "$WSLGIT_BINARY" "$OUTPUT_CMD_DIR" || exit 1
 cp resources/Fork.RI "$OUTPUT_CMD_DIR" || exit 1
+cp resources/wslgit-askpass "$OUTPUT_CMD_DIR" || exit 1
+cp resources/wslgit-ssh-agent "$OUTPUT_CMD_DIR" || exit 1
 cp resources/install.bat "$OUTPUT_DIR" || exit 1
 
 cd release && zip -r wslgit.zip ./*
@@ -67,6 +67,34 @@ if exist "%BINDIR%\Fork.RI" (
     )
 )
 
+echo.
+if exist "%BINDIR%\wslgit-askpass" (
+    echo 'bin\wslgit-askpass' already exist.
+) else (
+    echo Create 'bin\wslgit-askpass' symlink...
+    mklink "%BINDIR%\wslgit-askpass" "%CMDDIR%\wslgit-askpass"
+    if %ERRORLEVEL% neq 0 (
+        echo ERROR! Failed to create symlink '%BINDIR%\wslgit-askpass'.
+        goto :error
+    ) else (
+        echo OK.
+    )
+)
+
+echo.
+if exist "%BINDIR%\wslgit-ssh-agent" (
+    echo 'bin\wslgit-ssh-agent' already exist.
+) else (
+    echo Create 'bin\wslgit-ssh-agent' symlink...
+    mklink "%BINDIR%\wslgit-ssh-agent" "%CMDDIR%\wslgit-ssh-agent"
+    if %ERRORLEVEL% neq 0 (
+        echo ERROR! Failed to create symlink '%BINDIR%\wslgit-ssh-agent'.
+        goto :error
+    ) else (
+        echo OK.
+    )
+)
+
 echo.
 if exist "%BINDIR%\sh.exe" (
     echo 'bin\sh.exe' already exist.
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, UNIX_EPOCH};
+
+use cache;
+use invocation::Invocation;
+
+/// How long aliases are cached if the configuration of the repository does
+/// not change, so that changes to the global configuration are picked up.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Maximum number of aliases that are expanded in a row.
+const MAX_EXPANSIONS: usize = 16;
+
+/// Git commands that are never looked up as aliases, as listed by
+/// `git help -a`. Git ignores aliases that have the name of one of its
+/// commands, so there is no need to start WSL to read the configuration for
+/// them.
+const GIT_COMMANDS: &[&str] = &[
+    "add",
+    "am",
+    "annotate",
+    "apply",
+    "archimport",
+    "archive",
+    "bisect",
+    "blame",
+    "branch",
+    "bugreport",
+    "bundle",
+    "cat-file",
+    "check-attr",
+    "check-ignore",
+    "check-mailmap",
+    "check-ref-format",
+    "checkout",
+    "checkout-index",
+    "cherry",
+    "cherry-pick",
+    "citool",
+    "clean",
+    "clone",
+    "column",
+    "commit",
+    "commit-graph",
+    "commit-tree",
+    "config",
+    "count-objects",
+    "credential",
+    "credential-cache",
+    "credential-store",
+    "cvsexportcommit",
+    "cvsimport",
+    "cvsserver",
+    "daemon",
+    "describe",
+    "diagnose",
+    "diff",
+    "diff-files",
+    "diff-index",
+    "diff-tree",
+    "difftool",
+    "fast-export",
+    "fast-import",
+    "fetch",
+    "fetch-pack",
+    "filter-branch",
+    "fmt-merge-msg",
+    "for-each-ref",
+    "for-each-repo",
+    "fsck",
+    "gc",
+    "get-tar-commit-id",
+    "gitk",
+    "grep",
+    "gui",
+    "hash-object",
+    "help",
+    "hook",
+    "http-backend",
+    "imap-send",
+    "index-pack",
+    "init",
+    "instaweb",
+    "interpret-trailers",
+    "log",
+    "ls-files",
+    "ls-remote",
+    "ls-tree",
+    "mailinfo",
+    "mailsplit",
+    "maintenance",
+    "merge",
+    "merge-base",
+    "merge-file",
+    "merge-index",
+    "merge-one-file",
+    "merge-tree",
+    "mergetool",
+    "mktag",
+    "mktree",
+    "multi-pack-index",
+    "mv",
+    "name-rev",
+    "notes",
+    "p4",
+    "pack-objects",
+    "pack-redundant",
+    "pack-refs",
+    "patch-id",
+    "prune",
+    "prune-packed",
+    "pull",
+    "push",
+    "quiltimport",
+    "range-diff",
+    "read-tree",
+    "rebase",
+    "receive-pack",
+    "reflog",
+    "remote",
+    "repack",
+    "replace",
+    "request-pull",
+    "rerere",
+    "reset",
+    "restore",
+    "rev-list",
+    "rev-parse",
+    "revert",
+    "rm",
+    "send-email",
+    "send-pack",
+    "shortlog",
+    "show",
+    "show-branch",
+    "show-index",
+    "show-ref",
+    "sparse-checkout",
+    "stage",
+    "stash",
+    "status",
+    "stripspace",
+    "submodule",
+    "svn",
+    "switch",
+    "symbolic-ref",
+    "tag",
+    "unpack-file",
+    "unpack-objects",
+    "update-index",
+    "update-ref",
+    "update-server-info",
+    "upload-archive",
+    "upload-pack",
+    "var",
+    "verify-commit",
+    "verify-pack",
+    "verify-tag",
+    "version",
+    "whatchanged",
+    "worktree",
+    "write-tree",
+];
+
+/// Parse the output of `git config -z --get-regexp ^alias\.` into a map from
+/// alias name (in lower case) to its value.
+pub fn parse_aliases(config_output: &str) -> HashMap<String, String> {
+    config_output
+        .split('\0')
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('\n').unwrap_or((entry, ""));
+            let name = key.strip_prefix("alias.")?;
+            Some((name.to_lowercase(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Split the value of an alias into arguments the same way git does: at
+/// whitespace outside of quotes, with `"` and `'` quoting and `\` escaping
+/// the next character outside of single quotes.
+///
+/// Returns `None` if a quote is not closed.
+pub fn split_command_line(value: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => arg.push(c),
+            (_, '\\') => {
+                arg.push(chars.next()?);
+                in_arg = true;
+            }
+            (Some(_), _) => arg.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, _) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            (None, _) => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    Some(args)
+}
+
+/// Expand the subcommand of `invocation` if it is one of `aliases`, until it
+/// is a git command.
+///
+/// Returns `None` if the subcommand is not an alias, or if the alias runs a
+/// shell command (`!cmd`) and cannot be expanded.
+pub fn expand(invocation: &Invocation, aliases: &HashMap<String, String>) -> Option<Invocation> {
+    let mut expanded: Option<Invocation> = None;
+    for _ in 0..MAX_EXPANSIONS {
+        let current = expanded.as_ref().unwrap_or(invocation);
+        let alias = match current.subcommand {
+            Some(ref subcommand) if !GIT_COMMANDS.contains(&subcommand.as_str()) => {
+                match aliases.get(&subcommand.to_lowercase()) {
+                    Some(alias) => alias,
+                    None => break,
+                }
+            }
+            _ => break,
+        };
+        if alias.starts_with('!') {
+            return None;
+        }
+
+        let mut args = current.global_options.clone();
+        args.extend(split_command_line(alias)?);
+        args.extend(current.args.iter().cloned());
+        if !current.pathspec.is_empty() {
+            args.push("--".to_string());
+            args.extend(current.pathspec.iter().cloned());
+        }
+        expanded = Some(Invocation::parse(&args));
+    }
+    expanded
+}
+
+/// Find the configuration file of the repository in `working_dir`, or in one
+/// of its parent directories.
+fn repository_config(working_dir: &Path, invocation: &Invocation) -> Option<PathBuf> {
+    if let Some(ref git_dir) = invocation.git_dir {
+        return Some(working_dir.join(git_dir).join("config"));
+    }
+    working_dir
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|git_dir| git_dir.is_dir())
+        .map(|git_dir| git_dir.join("config"))
+}
+
+/// Read all aliases with git inside WSL.
+///
+/// * `global_options` - The global options of the git command line, already
+///   translated for WSL, so that aliases from `-c` and the configuration of
+///   the repository in `-C` are found.
+fn read_aliases(dist: Option<&str>, global_options: &[String]) -> Option<String> {
+    let mut cmd = Command::new("wsl");
+    if let Some(dist) = dist {
+        cmd.arg("--distribution").arg(dist);
+    }
+    let output = cmd
+        .arg("-e")
+        .arg("git")
+        .args(global_options)
+        .args(["config", "-z", "--get-regexp", "^alias\\."])
+        .output()
+        .ok()?;
+    // Exit code 1 means that there are no aliases
+    match output.status.code() {
+        Some(0) | Some(1) => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => None,
+    }
+}
+
+/// Resolve the subcommand of `invocation` if it is an alias, see [`expand`].
+///
+/// The aliases are cached for each repository until its configuration
+/// changes, or for a minute at most.
+pub fn resolve(
+    invocation: &Invocation,
+    dist: Option<&str>,
+    global_options: &[String],
+    working_dir: &str,
+) -> Option<Invocation> {
+    match invocation.subcommand {
+        Some(ref subcommand) if !GIT_COMMANDS.contains(&subcommand.as_str()) => {}
+        _ => return None,
+    }
+
+    let working_dir = Path::new(working_dir);
+    let config = repository_config(working_dir, invocation);
+    let config_mtime = config
+        .as_ref()
+        .and_then(|config| fs::metadata(config).and_then(|m| m.modified()).ok())
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |mtime| mtime.as_secs());
+    // Hash the key, the paths might be too long for a file name
+    let key = format!(
+        "{}\0{}\0{}",
+        dist.unwrap_or_default(),
+        global_options.join("\0"),
+        config.as_deref().unwrap_or(working_dir).to_string_lossy()
+    );
+    let cache_key = format!("aliases-{:016x}", cache::hash(&key));
+
+    // The cached value starts with the modification time of the configuration
+    let cached = cache::read(&cache_key, CACHE_TTL).and_then(|cached| {
+        let (mtime, aliases) = cached.split_once('\n')?;
+        if mtime == config_mtime.to_string() {
+            Some(aliases.to_string())
+        } else {
+            None
+        }
+    });
+    let config_output = match cached {
+        Some(config_output) => config_output,
+        None => {
+            let config_output = read_aliases(dist, global_options)?;
+            cache::write(&cache_key, &format!("{}\n{}", config_mtime, config_output));
+            config_output
+        }
+    };
+    expand(invocation, &parse_aliases(&config_output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn aliases(aliases: &[(&str, &str)]) -> HashMap<String, String> {
+        aliases
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_config_output() {
+        let output = "alias.sync\npull --rebase\0alias.top\nrev-parse --show-toplevel\0\
+                      alias.multi\nlog\n--oneline\0alias.empty\0";
+        assert_eq!(
+            parse_aliases(output),
+            aliases(&[
+                ("sync", "pull --rebase"),
+                ("top", "rev-parse --show-toplevel"),
+                ("multi", "log\n--oneline"),
+                ("empty", ""),
+            ])
+        );
+        assert_eq!(parse_aliases(""), HashMap::new());
+        assert_eq!(
+            parse_aliases("alias.Top\nrev-parse\0"),
+            aliases(&[("top", "rev-parse")])
+        );
+    }
+
+    #[test]
+    fn split_alias_values() {
+        let cases: &[(&str, Option<&[&str]>)] = &[
+            ("pull --rebase", Some(&["pull", "--rebase"])),
+            ("  log \t --oneline  ", Some(&["log", "--oneline"])),
+            ("log --format='%h %s'", Some(&["log", "--format=%h %s"])),
+            (
+                r#"log --grep "a \"b\" c""#,
+                Some(&["log", "--grep", r#"a "b" c"#]),
+            ),
+            (r"commit -m a\ b", Some(&["commit", "-m", "a b"])),
+            (r"log --grep='a\b'", Some(&["log", r"--grep=a\b"])),
+            ("log ''", Some(&["log", ""])),
+            ("", Some(&[])),
+            ("log 'unclosed", None),
+        ];
+        for &(value, expected) in cases {
+            assert_eq!(
+                split_command_line(value),
+                expected.map(strings),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn expand_aliases() {
+        let aliases = aliases(&[
+            ("sync", "pull --rebase"),
+            ("top", "rev-parse --show-toplevel"),
+            ("up", "sync --autostash"),
+            ("conf", "-c core.pager= remote"),
+            ("loop", "loop"),
+            ("shell", "!git pull && git push"),
+            ("push", "log"),
+        ]);
+        let expand_args = |args: &[&str]| {
+            expand(&Invocation::parse(&strings(args)), &aliases)
+                .map(|invocation| (invocation.subcommand.unwrap(), invocation.args))
+        };
+
+        assert_eq!(
+            expand_args(&["sync", "origin"]),
+            Some(("pull".to_string(), strings(&["--rebase", "origin"])))
+        );
+        assert_eq!(
+            expand_args(&["-C", "repo", "top"]),
+            Some(("rev-parse".to_string(), strings(&["--show-toplevel"])))
+        );
+        assert_eq!(
+            expand_args(&["UP"]),
+            Some(("pull".to_string(), strings(&["--rebase", "--autostash"])))
+        );
+        assert_eq!(
+            expand_args(&["conf", "-v"]),
+            Some(("remote".to_string(), strings(&["-v"])))
+        );
+        assert_eq!(
+            expand_args(&["loop"]).map(|(cmd, _)| cmd),
+            Some("loop".to_string())
+        );
+        assert_eq!(expand_args(&["shell"]), None);
+        assert_eq!(expand_args(&["push"]), None);
+        assert_eq!(expand_args(&["status"]), None);
+        assert_eq!(expand_args(&["--version"]), None);
+
+        let invocation = expand(
+            &Invocation::parse(&strings(&["-C", "repo", "top", "--", "file"])),
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(invocation.directories, strings(&["repo"]));
+        assert_eq!(invocation.pathspec, strings(&["file"]));
+    }
+}
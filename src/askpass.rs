@@ -0,0 +1,242 @@
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// Option of `wslgit.exe` that forwards a password prompt to the askpass
+/// program of the Windows caller.
+pub const ASKPASS_OPTION: &str = "--askpass";
+
+/// File name of the script next to `wslgit.exe` that git inside WSL runs as
+/// askpass program, and which calls `wslgit.exe --askpass`.
+pub const SHIM_NAME: &str = "wslgit-askpass";
+
+/// Variable with the original askpass program of the Windows caller.
+const ORIGINAL_VAR: &str = "WSLGIT_ASKPASS";
+
+/// Variable with the path to `wslgit.exe` inside WSL, used by the shim.
+const EXE_VAR: &str = "WSLGIT_ASKPASS_EXE";
+
+/// Variables the askpass script of VSCode needs to reach VSCode.
+const VSCODE_VARS: &[&str] = &[
+    "VSCODE_GIT_ASKPASS_NODE",
+    "VSCODE_GIT_ASKPASS_MAIN",
+    "VSCODE_GIT_ASKPASS_EXTRA_ARGS",
+    "VSCODE_GIT_IPC_HANDLE",
+];
+
+fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let is_drive_path = bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    is_drive_path || path.starts_with(r"\\") || path.starts_with("//")
+}
+
+/// The askpass program of the Windows caller, from `GIT_ASKPASS` or
+/// `SSH_ASKPASS`, if it is a Windows path that git inside WSL cannot run.
+pub fn original(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    env("GIT_ASKPASS")
+        .or_else(|| env("SSH_ASKPASS"))
+        .filter(|askpass| is_windows_path(askpass))
+}
+
+/// The variables to share with WSL so that git and ssh run the shim at
+/// `shim`, which calls `wslgit.exe` at `wslgit` with the prompt.
+///
+/// Returns no variables if there is no Windows askpass program.
+pub fn shared_vars(
+    env: impl Fn(&str) -> Option<String>,
+    shim: &str,
+    wslgit: &str,
+) -> Vec<(&'static str, String)> {
+    let askpass = match original(&env) {
+        Some(askpass) => askpass,
+        None => return Vec::new(),
+    };
+    let mut vars = vec![
+        (ORIGINAL_VAR, askpass),
+        (EXE_VAR, wslgit.to_string()),
+        ("GIT_ASKPASS", shim.to_string()),
+        ("SSH_ASKPASS", shim.to_string()),
+    ];
+    // ssh only asks the askpass program without a terminal if required to
+    if env("SSH_ASKPASS_REQUIRE").is_none() {
+        vars.push(("SSH_ASKPASS_REQUIRE", "prefer".to_string()));
+    }
+    vars.extend(
+        VSCODE_VARS
+            .iter()
+            .filter_map(|&key| env(key).map(|value| (key, value))),
+    );
+    vars
+}
+
+/// The command that asks the Windows askpass program `askpass` for `prompt`.
+///
+/// The askpass script of VSCode is a shell script that Windows cannot run,
+/// so the node script it calls is run directly.
+pub fn command(askpass: &str, env: impl Fn(&str) -> Option<String>, prompt: &[String]) -> Command {
+    let node = env("VSCODE_GIT_ASKPASS_NODE");
+    let main = env("VSCODE_GIT_ASKPASS_MAIN");
+    match (node, main) {
+        (Some(node), Some(main)) if askpass.to_ascii_lowercase().ends_with(".sh") => {
+            let mut command = Command::new(node);
+            command.env("ELECTRON_RUN_AS_NODE", "1").arg(main);
+            if let Some(extra_args) = env("VSCODE_GIT_ASKPASS_EXTRA_ARGS") {
+                command.args(extra_args.split_whitespace());
+            }
+            command.args(prompt);
+            command
+        }
+        _ => {
+            let mut command = Command::new(askpass);
+            command.args(prompt);
+            command
+        }
+    }
+}
+
+/// Ask the askpass program of the Windows caller for `prompt` and return
+/// the answer.
+pub fn run(prompt: &[String]) -> Result<String, String> {
+    let askpass = env::var(ORIGINAL_VAR).map_err(|_| format!("{} is not set", ORIGINAL_VAR))?;
+    let mut command = command(&askpass, |key| env::var(key).ok(), prompt);
+
+    // VSCode writes the answer to this file instead of stdout
+    let pipe = env::temp_dir()
+        .join("wslgit")
+        .join(format!("askpass-{}", std::process::id()));
+    if let Some(dir) = pipe.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let output = command
+        .env("VSCODE_GIT_ASKPASS_PIPE", &pipe)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to run {}: {}", askpass, e));
+    let piped = fs::read_to_string(&pipe).unwrap_or_default();
+    let _ = fs::remove_file(&pipe);
+    let output = output?;
+    if !output.status.success() {
+        return Err(format!("{} failed with {}", askpass, output.status));
+    }
+
+    let answer = if piped.is_empty() {
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    } else {
+        piped
+    };
+    Ok(answer.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    const VSCODE_ASKPASS: &str =
+        r"c:\Program Files\Microsoft VS Code\resources\app\extensions\git\dist\askpass.sh";
+
+    #[test]
+    fn original_askpass() {
+        assert_eq!(
+            original(env_of(&[("GIT_ASKPASS", VSCODE_ASKPASS)])),
+            Some(VSCODE_ASKPASS.to_string())
+        );
+        assert_eq!(
+            original(env_of(&[("SSH_ASKPASS", r"\\server\askpass.exe")])),
+            Some(r"\\server\askpass.exe".to_string())
+        );
+        // Already usable inside WSL
+        assert_eq!(
+            original(env_of(&[("GIT_ASKPASS", "/usr/bin/askpass")])),
+            None
+        );
+        assert_eq!(original(env_of(&[])), None);
+    }
+
+    #[test]
+    fn share_askpass_vars() {
+        let env = env_of(&[
+            ("GIT_ASKPASS", VSCODE_ASKPASS),
+            ("VSCODE_GIT_ASKPASS_NODE", r"c:\Code.exe"),
+            ("VSCODE_GIT_IPC_HANDLE", r"\\.\pipe\vscode-git"),
+        ]);
+        assert_eq!(
+            shared_vars(
+                env,
+                "/mnt/c/wslgit/cmd/wslgit-askpass",
+                "/mnt/c/wslgit/cmd/wslgit.exe"
+            ),
+            vec![
+                ("WSLGIT_ASKPASS", VSCODE_ASKPASS.to_string()),
+                (
+                    "WSLGIT_ASKPASS_EXE",
+                    "/mnt/c/wslgit/cmd/wslgit.exe".to_string()
+                ),
+                (
+                    "GIT_ASKPASS",
+                    "/mnt/c/wslgit/cmd/wslgit-askpass".to_string()
+                ),
+                (
+                    "SSH_ASKPASS",
+                    "/mnt/c/wslgit/cmd/wslgit-askpass".to_string()
+                ),
+                ("SSH_ASKPASS_REQUIRE", "prefer".to_string()),
+                ("VSCODE_GIT_ASKPASS_NODE", r"c:\Code.exe".to_string()),
+                ("VSCODE_GIT_IPC_HANDLE", r"\\.\pipe\vscode-git".to_string()),
+            ]
+        );
+        let env = env_of(&[
+            ("SSH_ASKPASS", r"C:\askpass.exe"),
+            ("SSH_ASKPASS_REQUIRE", "force"),
+        ]);
+        assert_eq!(shared_vars(env, "/shim", "/wslgit.exe").len(), 4);
+        assert_eq!(shared_vars(env_of(&[]), "/shim", "/wslgit.exe"), vec![]);
+    }
+
+    #[test]
+    fn askpass_command() {
+        let prompt = vec!["Password for 'https://me@github.com': ".to_string()];
+        let vscode_env = env_of(&[
+            ("VSCODE_GIT_ASKPASS_NODE", r"c:\Code.exe"),
+            ("VSCODE_GIT_ASKPASS_MAIN", r"c:\askpass-main.js"),
+            (
+                "VSCODE_GIT_ASKPASS_EXTRA_ARGS",
+                "--ms-enable-electron-run-as-node",
+            ),
+        ]);
+
+        let command = command(VSCODE_ASKPASS, &vscode_env, &prompt);
+        assert_eq!(command.get_program(), r"c:\Code.exe");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![
+                OsStr::new(r"c:\askpass-main.js"),
+                OsStr::new("--ms-enable-electron-run-as-node"),
+                OsStr::new(&prompt[0]),
+            ]
+        );
+        assert!(command
+            .get_envs()
+            .any(|(key, value)| key == "ELECTRON_RUN_AS_NODE" && value == Some(OsStr::new("1"))));
+
+        // Other askpass programs are run directly
+        let command = super::command(r"C:\askpass.exe", &vscode_env, &prompt);
+        assert_eq!(command.get_program(), r"C:\askpass.exe");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![OsStr::new(&prompt[0])]
+        );
+        let command = super::command(VSCODE_ASKPASS, env_of(&[]), &prompt);
+        assert_eq!(command.get_program(), VSCODE_ASKPASS);
+    }
+}
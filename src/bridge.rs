@@ -0,0 +1,292 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use cache;
+use config::DistBridge;
+use translate::PathTranslator;
+use unc;
+
+/// Largest file that is copied into the distribution git runs in.
+const MAX_COPY_SIZE: u64 = 1024 * 1024;
+
+/// Copies older than this are removed when the next file is copied.
+const MAX_COPY_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// How a path into another distribution is made accessible to git.
+#[derive(Debug, PartialEq)]
+pub enum Bridge {
+    /// The path is available at this path in the distribution git runs in.
+    Path(String),
+    /// The file is copied to a temporary file.
+    Copy,
+}
+
+/// Decide how `path`, a UNC path into another distribution, is made
+/// accessible to git running in the distribution with the UNC prefix
+/// `dist_share`.
+///
+/// * `exists` - Whether a Windows path exists.
+/// * `file_size` - Size of the file at a Windows path, `None` if it is not a
+///   file.
+pub fn plan(
+    path: &str,
+    dist_share: &str,
+    mode: DistBridge,
+    exists: impl Fn(&str) -> bool,
+    file_size: impl Fn(&str) -> Option<u64>,
+) -> Result<Bridge, String> {
+    let unc_path = unc::parse(path).map_err(|e| format!("{}: {}", path, e))?;
+    let git_dist = unc::parse(dist_share).map_err(|e| format!("{}: {}", dist_share, e))?;
+    if mode == DistBridge::Off {
+        return Err(format!(
+            "{} is in the WSL distribution {}, but git runs in {}, \
+             set dist_bridge to \"mount\" or \"copy\" to access it",
+            path, unc_path.dist, git_dist.dist
+        ));
+    }
+
+    // The distribution might be mounted below /mnt/wsl, which all WSL2
+    // distributions share, e.g. with `/ /mnt/wsl/Debian none bind` in its fstab
+    let mounted = format!(
+        "{}\\mnt\\wsl\\{}{}",
+        dist_share,
+        unc_path.dist,
+        unc_path.path.replace('/', "\\")
+    );
+    if exists(&mounted) {
+        return Ok(Bridge::Path(format!(
+            "/mnt/wsl/{}{}",
+            unc_path.dist,
+            unc_path.path.replace('\\', "/")
+        )));
+    }
+    if mode == DistBridge::Mount {
+        return Err(format!(
+            "{} is in the WSL distribution {}, which is not mounted at /mnt/wsl/{} in {}",
+            path, unc_path.dist, unc_path.dist, git_dist.dist
+        ));
+    }
+
+    match file_size(path) {
+        Some(size) if size <= MAX_COPY_SIZE => Ok(Bridge::Copy),
+        Some(_) => Err(format!(
+            "{} is larger than {} KiB and cannot be copied into {}",
+            path,
+            MAX_COPY_SIZE / 1024,
+            git_dist.dist
+        )),
+        None => Err(format!(
+            "{} is not a file and cannot be copied into {}",
+            path, git_dist.dist
+        )),
+    }
+}
+
+/// Remove copies that are older than `MAX_COPY_AGE`.
+fn remove_old_copies(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let is_old = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > MAX_COPY_AGE);
+        if is_old {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Copy the file `path` to `dir` and return the path of the copy. The name of
+/// the file is kept.
+///
+/// The copy is written to a temporary file that is renamed when it is
+/// complete, so that git never reads a partial copy of another wslgit
+/// process. If the rename fails, e.g. because git is reading the previous
+/// copy on Windows, the temporary file is used.
+fn copy_file(path: &str, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    remove_old_copies(dir);
+
+    let file_name = Path::new(path)
+        .file_name()
+        .map_or("file".into(), |name| name.to_string_lossy());
+    let copy = dir.join(format!("{:016x}-{}", cache::hash(path), file_name));
+    let temp = cache::temp_path(&copy);
+    fs::copy(path, &temp)?;
+    // The copy keeps the modification time of the file, which would let other
+    // wslgit processes remove it as old before git reads it
+    fs::File::options()
+        .write(true)
+        .open(&temp)?
+        .set_modified(SystemTime::now())?;
+    match fs::rename(&temp, &copy) {
+        Ok(()) => Ok(copy),
+        Err(_) => Ok(temp),
+    }
+}
+
+/// Make `path`, a UNC path into another distribution than the one git runs
+/// in, accessible to git as configured with `mode`, see [`plan`].
+///
+/// Returns the path to pass to git. A copied file is only read by git,
+/// changes to it are not written back.
+pub fn bridge(path: &str, translator: &PathTranslator, mode: DistBridge) -> Result<String, String> {
+    let dist_share = translator
+        .dist_share()
+        .ok_or_else(|| format!("{} is in another WSL distribution", path))?;
+    let bridge = plan(
+        path,
+        dist_share,
+        mode,
+        |path| Path::new(path).exists(),
+        |path| {
+            fs::metadata(path)
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+        },
+    )?;
+    match bridge {
+        Bridge::Path(bridged) => Ok(bridged),
+        Bridge::Copy => {
+            let dir = env::temp_dir().join("wslgit").join("bridge");
+            let copy =
+                copy_file(path, &dir).map_err(|e| format!("failed to copy {}: {}", path, e))?;
+            let copy = copy.to_string_lossy();
+            translator
+                .to_unix(&copy)
+                .ok_or_else(|| format!("the copy {} cannot be accessed from WSL", copy))
+        }
+    }
+}
+
+/// Copy the file `path` into `/tmp/wslgit` of the distribution git runs in,
+/// and return the path of the copy inside WSL.
+///
+/// Used for files that git only reads, e.g. commit messages, on a drive that
+/// is not mounted in the distribution.
+pub fn copy_into_dist(path: &str, translator: &PathTranslator) -> Result<String, String> {
+    let dist_share = translator
+        .dist_share()
+        .ok_or_else(|| format!("{} cannot be copied into an unknown distribution", path))?;
+    let dir = Path::new(dist_share).join("tmp").join("wslgit");
+    let copy = copy_file(path, &dir).map_err(|e| format!("failed to copy {}: {}", path, e))?;
+    let copy = copy.to_string_lossy();
+    translator
+        .to_unix(&copy)
+        .ok_or_else(|| format!("the copy {} cannot be accessed from WSL", copy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARE: &str = r"\\wsl$\Ubuntu";
+
+    fn plan_for(
+        path: &str,
+        mode: DistBridge,
+        mounted: &[&str],
+        file_size: Option<u64>,
+    ) -> Result<Bridge, String> {
+        plan(
+            path,
+            SHARE,
+            mode,
+            |path| mounted.contains(&path),
+            |_| file_size,
+        )
+    }
+
+    #[test]
+    fn bridge_through_mount() {
+        let mounted = &[r"\\wsl$\Ubuntu\mnt\wsl\Debian\tmp\msg.txt"];
+        for &mode in &[DistBridge::Mount, DistBridge::Copy] {
+            assert_eq!(
+                plan_for(r"\\wsl$\Debian\tmp\msg.txt", mode, mounted, Some(10)),
+                Ok(Bridge::Path("/mnt/wsl/Debian/tmp/msg.txt".to_string()))
+            );
+            assert_eq!(
+                plan_for(
+                    "//wsl.localhost/Debian/tmp/msg.txt",
+                    mode,
+                    mounted,
+                    Some(10)
+                ),
+                Ok(Bridge::Path("/mnt/wsl/Debian/tmp/msg.txt".to_string()))
+            );
+        }
+        assert!(plan_for(
+            r"\\wsl$\Debian\tmp\other.txt",
+            DistBridge::Mount,
+            mounted,
+            Some(10)
+        )
+        .unwrap_err()
+        .contains("not mounted at /mnt/wsl/Debian in Ubuntu"));
+    }
+
+    #[test]
+    fn bridge_by_copy() {
+        let path = r"\\wsl$\Debian\tmp\msg.txt";
+        assert_eq!(
+            plan_for(path, DistBridge::Copy, &[], Some(MAX_COPY_SIZE)),
+            Ok(Bridge::Copy)
+        );
+        assert!(
+            plan_for(path, DistBridge::Copy, &[], Some(MAX_COPY_SIZE + 1))
+                .unwrap_err()
+                .contains("is larger than 1024 KiB")
+        );
+        assert!(plan_for(path, DistBridge::Copy, &[], None)
+            .unwrap_err()
+            .contains("is not a file"));
+    }
+
+    #[test]
+    fn bridge_disabled() {
+        assert_eq!(
+            plan_for(
+                r"\\wsl$\Debian\tmp\msg.txt",
+                DistBridge::Off,
+                &[r"\\wsl$\Ubuntu\mnt\wsl\Debian\tmp\msg.txt"],
+                Some(10)
+            ),
+            Err(r#"\\wsl$\Debian\tmp\msg.txt is in the WSL distribution Debian, but git runs in Ubuntu, set dist_bridge to "mount" or "copy" to access it"#.to_string())
+        );
+        assert!(plan_for(r"C:\tmp", DistBridge::Copy, &[], Some(10)).is_err());
+    }
+
+    #[test]
+    fn copy_old_file() {
+        let dir = env::temp_dir().join("wslgit").join("test-bridge");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let original = dir.join("msg.txt");
+        fs::write(&original, "message").unwrap();
+        let two_hours_ago = SystemTime::now() - 2 * MAX_COPY_AGE;
+        fs::File::options()
+            .write(true)
+            .open(&original)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+
+        let copies = dir.join("copies");
+        let copy = copy_file(&original.to_string_lossy(), &copies).unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "message");
+        // The copy is not removed as old by the next copy
+        copy_file(&original.to_string_lossy(), &copies).unwrap();
+        assert!(copy.exists());
+        assert_eq!(fs::read_dir(&copies).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
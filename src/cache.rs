@@ -0,0 +1,144 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Return the file used to cache the value stored under `key`.
+///
+/// All characters that are not safe to use in a file name are replaced by `_`.
+fn cache_file(key: &str) -> PathBuf {
+    let file_name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    env::temp_dir().join("wslgit").join(file_name)
+}
+
+/// Hash `value` with 64-bit FNV-1a, which unlike the hasher of the standard
+/// library gives the same result in every version of wslgit, so that it can be
+/// used in the names of files that outlive a process.
+pub fn hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Read the value cached under `key`.
+///
+/// Returns `None` if there is no cached value or if it is older than `ttl`.
+pub fn read(key: &str, ttl: Duration) -> Option<String> {
+    let path = cache_file(key);
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+    match SystemTime::now().duration_since(modified) {
+        Ok(age) if age > ttl => return None,
+        _ => {}
+    }
+    fs::read_to_string(&path).ok()
+}
+
+/// Number of temporary files written by this process, to name them uniquely.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Return a path next to `path` that no other thread or process writes to.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(
+        ".{}-{}.tmp",
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(file_name)
+}
+
+/// Cache `value` under `key`.
+///
+/// The value is written to a temporary file that is then renamed, so that
+/// other wslgit processes never read a partially written value.
+///
+/// Failing to write the cache is not an error, the value will simply be looked
+/// up again the next time.
+pub fn write(key: &str, value: &str) {
+    let path = cache_file(key);
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let temp = temp_path(&path);
+    if fs::write(&temp, value).is_err() || fs::rename(&temp, &path).is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_value() {
+        let key = "test/cache value";
+        write(key, "some\nvalue");
+        assert_eq!(
+            read(key, Duration::from_secs(60)),
+            Some("some\nvalue".to_string())
+        );
+
+        // Values older than the TTL are ignored
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(cache_file(key))
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+        assert_eq!(read(key, Duration::from_secs(60)), None);
+        assert_eq!(read("test/missing value", Duration::from_secs(60)), None);
+        fs::remove_file(cache_file(key)).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        use std::thread;
+
+        let key = "test/concurrent value";
+        let values: Vec<String> = (0..4).map(|i| i.to_string().repeat(100_000)).collect();
+        let writers: Vec<_> = values
+            .iter()
+            .cloned()
+            .map(|value| thread::spawn(move || (0..10).for_each(|_| write(key, &value))))
+            .collect();
+        // Readers only ever see a whole value
+        for _ in 0..100 {
+            if let Some(value) = read(key, Duration::from_secs(60)) {
+                assert!(values.contains(&value), "partial value of {}", value.len());
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let dir = cache_file(key).parent().unwrap().to_path_buf();
+        assert!(!fs::read_dir(dir).unwrap().flatten().any(|entry| entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("test_concurrent_value.")));
+        fs::remove_file(cache_file(key)).unwrap();
+    }
+
+    #[test]
+    fn stable_hash() {
+        let cases: &[(&str, u64)] = &[
+            ("", 0xcbf2_9ce4_8422_2325),
+            ("a", 0xaf63_dc4c_8601_ec8c),
+            ("foobar", 0x8594_4171_f739_67e8),
+        ];
+        for &(value, hash_value) in cases {
+            assert_eq!(hash(value), hash_value, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,896 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use dist_rules::{self, DistRule};
+use forward_env::{self, ForwardRule};
+
+/// Name of the configuration file, both next to `wslgit.exe` and in
+/// `%APPDATA%\wslgit\`.
+const CONFIG_FILE_NAME: &str = "wslgit.toml";
+
+/// When `git` is started through an interactive `bash`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InteractiveShell {
+    Never,
+    Always,
+    /// Only for commands that need it, e.g. `fetch` or `push`.
+    Smart,
+}
+
+impl fmt::Display for InteractiveShell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InteractiveShell::Never => write!(f, "false"),
+            InteractiveShell::Always => write!(f, "true"),
+            InteractiveShell::Smart => write!(f, "\"smart\""),
+        }
+    }
+}
+
+/// How paths into another WSL distribution than the one git runs in are made
+/// accessible to git.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistBridge {
+    /// Such paths are rejected.
+    Off,
+    /// Use the distribution mounted below `/mnt/wsl` in the distribution git
+    /// runs in.
+    Mount,
+    /// Like `Mount`, but copy small files to a temporary file if the
+    /// distribution is not mounted.
+    Copy,
+}
+
+impl DistBridge {
+    fn parse(value: &str) -> Option<DistBridge> {
+        match value {
+            "off" => Some(DistBridge::Off),
+            "mount" => Some(DistBridge::Mount),
+            "copy" => Some(DistBridge::Copy),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DistBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DistBridge::Off => write!(f, "\"off\""),
+            DistBridge::Mount => write!(f, "\"mount\""),
+            DistBridge::Copy => write!(f, "\"copy\""),
+        }
+    }
+}
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Environment(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::Default => write!(f, "default"),
+            Source::File(ref path) => write!(f, "{}", path.display()),
+            Source::Environment(name) => write!(f, "environment variable {}", name),
+        }
+    }
+}
+
+/// The value of a setting together with its source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Setting<T> {
+        Setting {
+            value,
+            source: Source::Default,
+        }
+    }
+
+    fn set(&mut self, value: T, source: Source) {
+        self.value = value;
+        self.source = source;
+    }
+}
+
+/// An invalid configuration file or environment variable.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub source: Source,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+/// The effective configuration of wslgit.
+///
+/// Every setting is taken from the first of these that sets it:
+/// 1. The `WSLGIT_*` environment variable.
+/// 2. `%APPDATA%\wslgit\wslgit.toml`.
+/// 3. `wslgit.toml` next to `wslgit.exe`.
+/// 4. The default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// `use_interactive_shell` or `WSLGIT_USE_INTERACTIVE_SHELL`.
+    pub use_interactive_shell: Setting<InteractiveShell>,
+    /// `default_dist` or `WSLGIT_DEFAULT_DIST`.
+    pub default_dist: Setting<Option<String>>,
+    /// `enable_logging` or `WSLGIT_ENABLE_LOGGING`.
+    pub enable_logging: Setting<bool>,
+    /// `translate_output_cmds` or `WSLGIT_TRANSLATE_OUTPUT_CMDS`.
+    pub translate_output_cmds: Setting<Vec<String>>,
+    /// `dist_rules` or `WSLGIT_DIST_RULES`.
+    pub dist_rules: Setting<Vec<DistRule>>,
+    /// `dist_bridge` or `WSLGIT_DIST_BRIDGE`.
+    pub dist_bridge: Setting<DistBridge>,
+    /// `credential_helper` or `WSLGIT_CREDENTIAL_HELPER`.
+    pub credential_helper: Setting<Option<String>>,
+    /// `ssh_agent_pipe` or `WSLGIT_SSH_AGENT_PIPE`.
+    pub ssh_agent_pipe: Setting<Option<String>>,
+    /// `forward_env` or `WSLGIT_FORWARD_ENV`.
+    pub forward_env: Setting<Vec<ForwardRule>>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            use_interactive_shell: Setting::new(InteractiveShell::Smart),
+            default_dist: Setting::new(None),
+            enable_logging: Setting::new(false),
+            translate_output_cmds: Setting::new(Vec::new()),
+            dist_rules: Setting::new(Vec::new()),
+            dist_bridge: Setting::new(DistBridge::Off),
+            credential_helper: Setting::new(None),
+            ssh_agent_pipe: Setting::new(None),
+            forward_env: Setting::new(forward_env::default_rules()),
+        }
+    }
+}
+
+/// Return the configuration files in the order they are applied.
+fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        files.push(exe_dir.join(CONFIG_FILE_NAME));
+    }
+    if let Some(app_data) = env::var_os("APPDATA") {
+        files.push(
+            PathBuf::from(app_data)
+                .join("wslgit")
+                .join(CONFIG_FILE_NAME),
+        );
+    }
+    files
+}
+
+/// Split a comma separated list, ignoring empty entries.
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+impl Config {
+    /// Load the configuration from the configuration files and the
+    /// environment.
+    pub fn load() -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        for path in config_files() {
+            if let Ok(contents) = fs::read_to_string(&path) {
+                config.apply_file(&path, &contents)?;
+            }
+        }
+        config.apply_env(|key| env::var(key).ok())?;
+        Ok(config)
+    }
+
+    /// Apply the settings from the contents of the configuration file `path`.
+    fn apply_file(&mut self, path: &Path, contents: &str) -> Result<(), ConfigError> {
+        let error = |message: String| ConfigError {
+            source: Source::File(path.to_path_buf()),
+            message,
+        };
+        let table = match contents.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return Err(error("expected a table".to_string())),
+            Err(e) => return Err(error(e.to_string())),
+        };
+        let source = Source::File(path.to_path_buf());
+
+        for (key, value) in &table {
+            match key.as_str() {
+                "use_interactive_shell" => {
+                    let mode = match *value {
+                        Value::Boolean(true) => InteractiveShell::Always,
+                        Value::Boolean(false) => InteractiveShell::Never,
+                        Value::String(ref s) if s == "smart" => InteractiveShell::Smart,
+                        _ => {
+                            return Err(error(format!(
+                                "`{}` must be true, false or \"smart\"",
+                                key
+                            )))
+                        }
+                    };
+                    self.use_interactive_shell.set(mode, source.clone());
+                }
+                "default_dist" => match *value {
+                    Value::String(ref dist) if !dist.is_empty() => {
+                        self.default_dist.set(Some(dist.clone()), source.clone())
+                    }
+                    _ => {
+                        return Err(error(format!(
+                            "`{}` must be the name of a WSL distribution",
+                            key
+                        )))
+                    }
+                },
+                "enable_logging" => match *value {
+                    Value::Boolean(enable) => self.enable_logging.set(enable, source.clone()),
+                    _ => return Err(error(format!("`{}` must be true or false", key))),
+                },
+                "translate_output_cmds" => {
+                    let cmds: Option<Vec<String>> = value.as_array().and_then(|cmds| {
+                        cmds.iter()
+                            .map(|cmd| cmd.as_str().map(|cmd| cmd.to_string()))
+                            .collect()
+                    });
+                    match cmds {
+                        Some(cmds) => self.translate_output_cmds.set(cmds, source.clone()),
+                        None => {
+                            return Err(error(format!(
+                                "`{}` must be a list of git subcommands",
+                                key
+                            )))
+                        }
+                    }
+                }
+                "dist_rules" => {
+                    let rules: Option<Vec<DistRule>> = value.as_array().and_then(|rules| {
+                        rules
+                            .iter()
+                            .map(|rule| {
+                                let pattern = rule.get("path")?.as_str()?;
+                                let dist = rule.get("dist")?.as_str()?;
+                                if rule.as_table()?.len() != 2 {
+                                    return None;
+                                }
+                                Some(DistRule {
+                                    pattern: pattern.to_string(),
+                                    dist: dist.to_string(),
+                                })
+                            })
+                            .collect()
+                    });
+                    match rules {
+                        Some(rules) => self.dist_rules.set(rules, source.clone()),
+                        None => {
+                            return Err(error(format!(
+                                "`{}` must be a list of tables with `path` and `dist`",
+                                key
+                            )))
+                        }
+                    }
+                }
+                "dist_bridge" => match value.as_str().and_then(DistBridge::parse) {
+                    Some(bridge) => self.dist_bridge.set(bridge, source.clone()),
+                    None => {
+                        return Err(error(format!(
+                            "`{}` must be \"off\", \"mount\" or \"copy\"",
+                            key
+                        )))
+                    }
+                },
+                "credential_helper" => match *value {
+                    Value::String(ref helper) if !helper.is_empty() => self
+                        .credential_helper
+                        .set(Some(helper.clone()), source.clone()),
+                    _ => {
+                        return Err(error(format!(
+                            "`{}` must be the path of a credential helper executable",
+                            key
+                        )))
+                    }
+                },
+                "ssh_agent_pipe" => match *value {
+                    Value::String(ref pipe) if !pipe.is_empty() => {
+                        self.ssh_agent_pipe.set(Some(pipe.clone()), source.clone())
+                    }
+                    _ => {
+                        return Err(error(format!(
+                            "`{}` must be the path of the named pipe of an ssh-agent",
+                            key
+                        )))
+                    }
+                },
+                "forward_env" => {
+                    let rules: Option<Result<Vec<ForwardRule>, String>> =
+                        value.as_array().and_then(|rules| {
+                            rules
+                                .iter()
+                                .map(|rule| rule.as_str().map(ForwardRule::parse))
+                                .collect()
+                        });
+                    match rules {
+                        Some(Ok(rules)) => self.forward_env.set(rules, source.clone()),
+                        Some(Err(message)) => return Err(error(message)),
+                        None => {
+                            return Err(error(format!(
+                                "`{}` must be a list of environment variables",
+                                key
+                            )))
+                        }
+                    }
+                }
+                _ => return Err(error(format!("unknown setting `{}`", key))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the settings from the `WSLGIT_*` environment variables, looked
+    /// up with `var`.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(interactive_flag) = var("WSLGIT_USE_INTERACTIVE_SHELL") {
+            let mode = if interactive_flag == "false" || interactive_flag == "0" {
+                InteractiveShell::Never
+            } else if interactive_flag == "smart" {
+                InteractiveShell::Smart
+            } else {
+                InteractiveShell::Always
+            };
+            self.use_interactive_shell
+                .set(mode, Source::Environment("WSLGIT_USE_INTERACTIVE_SHELL"));
+        }
+        if let Some(default_dist) = var("WSLGIT_DEFAULT_DIST") {
+            self.default_dist.set(
+                Some(default_dist),
+                Source::Environment("WSLGIT_DEFAULT_DIST"),
+            );
+        }
+        if let Some(enable_log_flag) = var("WSLGIT_ENABLE_LOGGING") {
+            self.enable_logging.set(
+                enable_log_flag == "true" || enable_log_flag == "1",
+                Source::Environment("WSLGIT_ENABLE_LOGGING"),
+            );
+        }
+        if let Some(cmds) = var("WSLGIT_TRANSLATE_OUTPUT_CMDS") {
+            self.translate_output_cmds.set(
+                split_list(&cmds),
+                Source::Environment("WSLGIT_TRANSLATE_OUTPUT_CMDS"),
+            );
+        }
+        if let Some(rules) = var("WSLGIT_DIST_RULES") {
+            let source = Source::Environment("WSLGIT_DIST_RULES");
+            match dist_rules::parse_rules(&rules) {
+                Ok(rules) => self.dist_rules.set(rules, source),
+                Err(message) => return Err(ConfigError { source, message }),
+            }
+        }
+        if let Some(helper) = var("WSLGIT_CREDENTIAL_HELPER") {
+            self.credential_helper.set(
+                Some(helper).filter(|helper| !helper.is_empty()),
+                Source::Environment("WSLGIT_CREDENTIAL_HELPER"),
+            );
+        }
+        if let Some(pipe) = var("WSLGIT_SSH_AGENT_PIPE") {
+            self.ssh_agent_pipe.set(
+                Some(pipe).filter(|pipe| !pipe.is_empty()),
+                Source::Environment("WSLGIT_SSH_AGENT_PIPE"),
+            );
+        }
+        if let Some(rules) = var("WSLGIT_FORWARD_ENV") {
+            let source = Source::Environment("WSLGIT_FORWARD_ENV");
+            let rules: Result<Vec<ForwardRule>, String> = split_list(&rules)
+                .iter()
+                .map(|rule| ForwardRule::parse(rule))
+                .collect();
+            match rules {
+                Ok(rules) => self.forward_env.set(rules, source),
+                Err(message) => return Err(ConfigError { source, message }),
+            }
+        }
+        if let Some(bridge) = var("WSLGIT_DIST_BRIDGE") {
+            let source = Source::Environment("WSLGIT_DIST_BRIDGE");
+            match DistBridge::parse(&bridge) {
+                Some(bridge) => self.dist_bridge.set(bridge, source),
+                None => {
+                    return Err(ConfigError {
+                        source,
+                        message: "must be \"off\", \"mount\" or \"copy\"".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return a lookup of the environment variables `vars`, for tests that must
+/// not depend on the environment of the test process.
+#[cfg(test)]
+pub fn env_vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: Vec<(String, String)> = vars
+        .iter()
+        .map(|&(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    move |key| {
+        vars.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// The default configuration with the settings of the environment
+    /// variables `vars`, without reading any configuration file.
+    pub fn with_env(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        config.apply_env(env_vars(vars))?;
+        Ok(config)
+    }
+}
+
+impl fmt::Display for Config {
+    /// Format the effective configuration as a configuration file, with the
+    /// source of each value as a comment.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "use_interactive_shell = {}  # {}",
+            self.use_interactive_shell.value, self.use_interactive_shell.source
+        )?;
+        match self.default_dist.value {
+            Some(ref dist) => writeln!(
+                f,
+                "default_dist = {}  # {}",
+                Value::String(dist.clone()),
+                self.default_dist.source
+            )?,
+            None => writeln!(
+                f,
+                "# default_dist is not set, the default WSL distribution is used  # {}",
+                self.default_dist.source
+            )?,
+        }
+        writeln!(
+            f,
+            "enable_logging = {}  # {}",
+            self.enable_logging.value, self.enable_logging.source
+        )?;
+        let cmds: Vec<Value> = self
+            .translate_output_cmds
+            .value
+            .iter()
+            .map(|cmd| Value::String(cmd.clone()))
+            .collect();
+        writeln!(
+            f,
+            "translate_output_cmds = {}  # {}",
+            Value::Array(cmds),
+            self.translate_output_cmds.source
+        )?;
+        writeln!(
+            f,
+            "dist_bridge = {}  # {}",
+            self.dist_bridge.value, self.dist_bridge.source
+        )?;
+        match self.credential_helper.value {
+            Some(ref helper) => writeln!(
+                f,
+                "credential_helper = {}  # {}",
+                Value::String(helper.clone()),
+                self.credential_helper.source
+            )?,
+            None => writeln!(
+                f,
+                "# credential_helper is not set, git uses the helpers configured in WSL  # {}",
+                self.credential_helper.source
+            )?,
+        }
+        match self.ssh_agent_pipe.value {
+            Some(ref pipe) => writeln!(
+                f,
+                "ssh_agent_pipe = {}  # {}",
+                Value::String(pipe.clone()),
+                self.ssh_agent_pipe.source
+            )?,
+            None => writeln!(
+                f,
+                "# ssh_agent_pipe is not set, the Windows ssh-agent is not forwarded  # {}",
+                self.ssh_agent_pipe.source
+            )?,
+        }
+        let rules: Vec<Value> = self
+            .forward_env
+            .value
+            .iter()
+            .map(|rule| Value::String(rule.to_string()))
+            .collect();
+        writeln!(
+            f,
+            "forward_env = {}  # {}",
+            Value::Array(rules),
+            self.forward_env.source
+        )?;
+        let rules: Vec<String> = self
+            .dist_rules
+            .value
+            .iter()
+            .map(|rule| {
+                format!(
+                    "{{ path = {}, dist = {} }}",
+                    Value::String(rule.pattern.clone()),
+                    Value::String(rule.dist.clone())
+                )
+            })
+            .collect();
+        writeln!(
+            f,
+            "dist_rules = [{}]  # {}",
+            rules.join(", "),
+            self.dist_rules.source
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_file(config: &mut Config, path: &str, contents: &str) -> Result<(), ConfigError> {
+        config.apply_file(Path::new(path), contents)
+    }
+
+    #[test]
+    fn read_config_file() {
+        let mut config = Config::default();
+        apply_file(
+            &mut config,
+            "wslgit.toml",
+            r#"
+            use_interactive_shell = false
+            default_dist = "Ubuntu"
+            enable_logging = true
+            translate_output_cmds = ["worktree", "submodule"]
+            credential_helper = 'C:\Program Files\Git\mingw64\bin\git-credential-manager.exe'
+            ssh_agent_pipe = '\\.\pipe\openssh-ssh-agent'
+            "#,
+        )
+        .unwrap();
+        let source = Source::File(PathBuf::from("wslgit.toml"));
+        assert_eq!(
+            config.use_interactive_shell,
+            Setting {
+                value: InteractiveShell::Never,
+                source: source.clone()
+            }
+        );
+        assert_eq!(
+            config.default_dist,
+            Setting {
+                value: Some("Ubuntu".to_string()),
+                source: source.clone()
+            }
+        );
+        assert_eq!(
+            config.enable_logging,
+            Setting {
+                value: true,
+                source: source.clone()
+            }
+        );
+        assert_eq!(
+            config.translate_output_cmds,
+            Setting {
+                value: vec!["worktree".to_string(), "submodule".to_string()],
+                source: source.clone()
+            }
+        );
+        assert_eq!(
+            config.credential_helper,
+            Setting {
+                value: Some(
+                    r"C:\Program Files\Git\mingw64\bin\git-credential-manager.exe".to_string()
+                ),
+                source: source.clone()
+            }
+        );
+        assert_eq!(
+            config.ssh_agent_pipe,
+            Setting {
+                value: Some(r"\\.\pipe\openssh-ssh-agent".to_string()),
+                source
+            }
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let mut config = Config::default();
+        apply_file(
+            &mut config,
+            "exe/wslgit.toml",
+            "use_interactive_shell = true\ndefault_dist = \"Debian\"\nenable_logging = true",
+        )
+        .unwrap();
+        apply_file(
+            &mut config,
+            "appdata/wslgit.toml",
+            "use_interactive_shell = \"smart\"\ndefault_dist = \"Ubuntu\"",
+        )
+        .unwrap();
+        config
+            .apply_env(env_vars(&[("WSLGIT_DEFAULT_DIST", "Alpine")]))
+            .unwrap();
+
+        assert_eq!(config.use_interactive_shell.value, InteractiveShell::Smart);
+        assert_eq!(
+            config.use_interactive_shell.source,
+            Source::File(PathBuf::from("appdata/wslgit.toml"))
+        );
+        assert_eq!(config.default_dist.value, Some("Alpine".to_string()));
+        assert_eq!(
+            config.default_dist.source,
+            Source::Environment("WSLGIT_DEFAULT_DIST")
+        );
+        assert!(config.enable_logging.value);
+        assert_eq!(
+            config.enable_logging.source,
+            Source::File(PathBuf::from("exe/wslgit.toml"))
+        );
+        assert_eq!(config.translate_output_cmds.value, Vec::<String>::new());
+        assert_eq!(config.translate_output_cmds.source, Source::Default);
+    }
+
+    #[test]
+    fn environment_variables() {
+        let cases: &[(&str, InteractiveShell)] = &[
+            ("false", InteractiveShell::Never),
+            ("0", InteractiveShell::Never),
+            ("smart", InteractiveShell::Smart),
+            ("true", InteractiveShell::Always),
+            ("", InteractiveShell::Always),
+        ];
+        for &(value, expected) in cases {
+            let config = Config::with_env(&[("WSLGIT_USE_INTERACTIVE_SHELL", value)]).unwrap();
+            assert_eq!(config.use_interactive_shell.value, expected, "{}", value);
+        }
+
+        let config = Config::with_env(&[
+            ("WSLGIT_TRANSLATE_OUTPUT_CMDS", "worktree, submodule,"),
+            ("WSLGIT_ENABLE_LOGGING", "1"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.translate_output_cmds.value,
+            vec!["worktree".to_string(), "submodule".to_string()]
+        );
+        assert!(config.enable_logging.value);
+
+        let config = Config::with_env(&[("WSLGIT_DIST_BRIDGE", "copy")]).unwrap();
+        assert_eq!(config.dist_bridge.value, DistBridge::Copy);
+        assert_eq!(
+            Config::with_env(&[("WSLGIT_DIST_BRIDGE", "yes")])
+                .unwrap_err()
+                .source,
+            Source::Environment("WSLGIT_DIST_BRIDGE")
+        );
+
+        let mut config =
+            Config::with_env(&[("WSLGIT_SSH_AGENT_PIPE", r"\\.\pipe\pageant")]).unwrap();
+        assert_eq!(
+            config.ssh_agent_pipe.value,
+            Some(r"\\.\pipe\pageant".to_string())
+        );
+        config
+            .apply_env(env_vars(&[("WSLGIT_SSH_AGENT_PIPE", "")]))
+            .unwrap();
+        assert_eq!(config.ssh_agent_pipe.value, None);
+
+        let mut config =
+            Config::with_env(&[("WSLGIT_FORWARD_ENV", "GIT_TRACE, GIT_DIR/p,")]).unwrap();
+        assert_eq!(
+            config.forward_env.value,
+            vec![
+                ForwardRule::parse("GIT_TRACE").unwrap(),
+                ForwardRule::parse("GIT_DIR/p").unwrap(),
+            ]
+        );
+        config
+            .apply_env(env_vars(&[("WSLGIT_FORWARD_ENV", "")]))
+            .unwrap();
+        assert_eq!(config.forward_env.value, vec![]);
+        assert_eq!(
+            Config::with_env(&[("WSLGIT_FORWARD_ENV", "GIT_DIR/x")])
+                .unwrap_err()
+                .source,
+            Source::Environment("WSLGIT_FORWARD_ENV")
+        );
+
+        // Unset variables keep the defaults
+        assert_eq!(Config::with_env(&[]), Ok(Config::default()));
+    }
+
+    #[test]
+    fn invalid_config_files() {
+        let cases: &[(&str, &str)] = &[
+            (
+                "use_interactive_shell = \"sometimes\"",
+                "`use_interactive_shell` must be true, false or \"smart\"",
+            ),
+            (
+                "default_dist = 1",
+                "`default_dist` must be the name of a WSL distribution",
+            ),
+            (
+                "enable_logging = \"yes\"",
+                "`enable_logging` must be true or false",
+            ),
+            (
+                "translate_output_cmds = \"worktree\"",
+                "`translate_output_cmds` must be a list of git subcommands",
+            ),
+            (
+                "translate_output_cmds = [\"worktree\", 1]",
+                "`translate_output_cmds` must be a list of git subcommands",
+            ),
+            (
+                "dist_bridge = true",
+                "`dist_bridge` must be \"off\", \"mount\" or \"copy\"",
+            ),
+            (
+                "dist_bridge = \"always\"",
+                "`dist_bridge` must be \"off\", \"mount\" or \"copy\"",
+            ),
+            (
+                "credential_helper = \"\"",
+                "`credential_helper` must be the path of a credential helper executable",
+            ),
+            (
+                "ssh_agent_pipe = false",
+                "`ssh_agent_pipe` must be the path of the named pipe of an ssh-agent",
+            ),
+            (
+                "forward_env = \"GIT_*\"",
+                "`forward_env` must be a list of environment variables",
+            ),
+            (
+                "forward_env = [\"GIT_DIR/x\"]",
+                "invalid variable `GIT_DIR/x`, expected `<name>` or `<name>/<flags>` with flags of p, l, u and w",
+            ),
+            (
+                "use_interactive = true",
+                "unknown setting `use_interactive`",
+            ),
+        ];
+        for &(contents, message) in cases {
+            assert_eq!(
+                apply_file(&mut Config::default(), "wslgit.toml", contents),
+                Err(ConfigError {
+                    source: Source::File(PathBuf::from("wslgit.toml")),
+                    message: message.to_string()
+                }),
+                "{}",
+                contents
+            );
+        }
+
+        let error =
+            apply_file(&mut Config::default(), "wslgit.toml", "enable_logging = ").unwrap_err();
+        assert!(error.to_string().starts_with("wslgit.toml: "));
+    }
+
+    #[test]
+    fn dist_rules() {
+        let mut config = Config::default();
+        apply_file(
+            &mut config,
+            "wslgit.toml",
+            r#"
+            [[dist_rules]]
+            path = 'D:\work'
+            dist = "Ubuntu-WSL1"
+
+            [[dist_rules]]
+            path = 'C:\src\*\legacy'
+            dist = "Debian"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.dist_rules.value,
+            vec![
+                DistRule {
+                    pattern: r"D:\work".to_string(),
+                    dist: "Ubuntu-WSL1".to_string()
+                },
+                DistRule {
+                    pattern: r"C:\src\*\legacy".to_string(),
+                    dist: "Debian".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            config.to_string().lines().last().unwrap(),
+            r#"dist_rules = [{ path = "D:\\work", dist = "Ubuntu-WSL1" }, { path = "C:\\src\\*\\legacy", dist = "Debian" }]  # wslgit.toml"#
+        );
+
+        // The environment variable replaces all rules
+        config
+            .apply_env(env_vars(&[("WSLGIT_DIST_RULES", r"E:\repos=Alpine")]))
+            .unwrap();
+        assert_eq!(
+            config.dist_rules.value,
+            vec![DistRule {
+                pattern: r"E:\repos".to_string(),
+                dist: "Alpine".to_string()
+            }]
+        );
+
+        assert_eq!(
+            config.apply_env(env_vars(&[("WSLGIT_DIST_RULES", r"E:\repos")])),
+            Err(ConfigError {
+                source: Source::Environment("WSLGIT_DIST_RULES"),
+                message: r"invalid rule `E:\repos`, expected `<path>=<distribution>`".to_string()
+            })
+        );
+
+        for contents in &[
+            "dist_rules = 'D:\\work=Ubuntu'",
+            "[[dist_rules]]\npath = 'D:\\work'",
+            "[[dist_rules]]\npath = 'D:\\work'\ndist = 'Ubuntu'\nwsl = 1",
+        ] {
+            assert_eq!(
+                apply_file(&mut Config::default(), "wslgit.toml", contents)
+                    .unwrap_err()
+                    .message,
+                "`dist_rules` must be a list of tables with `path` and `dist`"
+            );
+        }
+    }
+
+    #[test]
+    fn show_config() {
+        let mut config = Config::default();
+        apply_file(
+            &mut config,
+            "wslgit.toml",
+            "default_dist = \"Ubuntu\"\ntranslate_output_cmds = [\"worktree\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            config.to_string(),
+            "use_interactive_shell = \"smart\"  # default\n\
+             default_dist = \"Ubuntu\"  # wslgit.toml\n\
+             enable_logging = false  # default\n\
+             translate_output_cmds = [\"worktree\"]  # wslgit.toml\n\
+             dist_bridge = \"off\"  # default\n\
+             # credential_helper is not set, git uses the helpers configured in WSL  # default\n\
+             # ssh_agent_pipe is not set, the Windows ssh-agent is not forwarded  # default\n\
+             forward_env = [\"GIT_*\", \"GIT_DIR/p\", \"GIT_WORK_TREE/p\", \"GIT_INDEX_FILE/p\", \"GIT_OBJECT_DIRECTORY/p\", \"GIT_COMMON_DIR/p\", \"GIT_CONFIG_GLOBAL/p\", \"GIT_CONFIG_SYSTEM/p\", \"GIT_TEMPLATE_DIR/p\", \"GIT_ALTERNATE_OBJECT_DIRECTORIES/l\", \"GIT_CEILING_DIRECTORIES/l\"]  # default\n\
+             dist_rules = []  # default\n"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use shell;
+
+/// Actions of the credential helper protocol that are relayed. Helpers must
+/// ignore any other action.
+const ACTIONS: &[&str] = &["get", "store", "erase"];
+
+/// Option of `wslgit.exe` that runs it as a credential helper.
+pub const HELPER_OPTION: &str = "--credential-helper";
+
+/// File name (without extension) under which `wslgit.exe` runs as a credential
+/// helper, so that it can be installed as `git-credential-wslgit`.
+pub const HELPER_NAME: &str = "git-credential-wslgit";
+
+/// Attributes of the credential helper protocol, e.g. `host=github.com`, in
+/// their original order.
+pub type Attributes = Vec<(String, String)>;
+
+/// Parse a block of `key=value` lines of the credential helper protocol. The
+/// block ends at an empty line or at the end of `input`.
+pub fn parse(input: &str) -> Result<Attributes, String> {
+    input
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(|line| match line.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(format!("invalid credential attribute {:?}", line)),
+        })
+        .collect()
+}
+
+/// Format `attributes` as a block of the credential helper protocol.
+pub fn format(attributes: &[(String, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(key, value)| format!("{}={}\n", key, value))
+        .collect()
+}
+
+/// Return `attributes` with the secrets replaced, for logging.
+pub fn redact(attributes: &[(String, String)]) -> Attributes {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let is_secret = key == "password" || key.starts_with("oauth_");
+            let value = if is_secret { "<redacted>" } else { value };
+            (key.clone(), value.to_string())
+        })
+        .collect()
+}
+
+/// The value of `credential.helper` that makes git inside WSL call
+/// `wslgit.exe`, at the path `wslgit_path` inside WSL, as its credential
+/// helper. Git appends the action to it.
+pub fn helper_config(wslgit_path: &str) -> String {
+    format!("!{} {}", shell::quote(wslgit_path), HELPER_OPTION)
+}
+
+/// Run the Windows credential helper `helper` with `action` and `request` on
+/// its stdin, and return the attributes it prints.
+fn run_helper(
+    helper: &str,
+    action: &str,
+    request: &[(String, String)],
+) -> Result<Attributes, String> {
+    let mut process = Command::new(helper)
+        .arg(action)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", helper, e))?;
+    // The request is small enough to be written before reading the response
+    let mut stdin = process.stdin.take().unwrap();
+    stdin
+        .write_all(format(request).as_bytes())
+        .and_then(|_| stdin.write_all(b"\n"))
+        .map_err(|e| format!("failed to write to {}: {}", helper, e))?;
+    drop(stdin);
+    let output = process
+        .wait_with_output()
+        .map_err(|e| format!("failed to run {}: {}", helper, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed with {}",
+            helper, action, output.status
+        ));
+    }
+    parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Relay the request for `action` from `input` to the Windows credential
+/// helper `helper`, and its response to `output`.
+///
+/// Returns the request and the response for logging.
+pub fn relay<R: Read, W: Write>(
+    helper: &str,
+    action: &str,
+    input: &mut R,
+    output: &mut W,
+) -> Result<(Attributes, Attributes), String> {
+    if !ACTIONS.contains(&action) {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let mut request = String::new();
+    input
+        .read_to_string(&mut request)
+        .map_err(|e| format!("failed to read the request: {}", e))?;
+    let request = parse(&request)?;
+    let response = run_helper(helper, action, &request)?;
+    // Only `get` has a response
+    if action == "get" {
+        output
+            .write_all(format(&response).as_bytes())
+            .map_err(|e| format!("failed to write the response: {}", e))?;
+    }
+    Ok((request, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(attributes: &[(&str, &str)]) -> Attributes {
+        attributes
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_attributes() {
+        assert_eq!(
+            parse("protocol=https\nhost=github.com\npath=a/b=c.git\n\n"),
+            Ok(attributes(&[
+                ("protocol", "https"),
+                ("host", "github.com"),
+                ("path", "a/b=c.git"),
+            ]))
+        );
+        assert_eq!(
+            parse("username=me\r\npassword=\r\n"),
+            Ok(attributes(&[("username", "me"), ("password", "")]))
+        );
+        assert_eq!(
+            parse("wwwauth[]=Basic\nwwwauth[]=Bearer\n"),
+            Ok(attributes(&[
+                ("wwwauth[]", "Basic"),
+                ("wwwauth[]", "Bearer")
+            ]))
+        );
+        // Everything after the empty line is ignored
+        assert_eq!(
+            parse("host=a\n\nnot an attribute\n"),
+            Ok(attributes(&[("host", "a")]))
+        );
+        assert_eq!(parse(""), Ok(Vec::new()));
+        assert!(parse("host\n").is_err());
+        assert!(parse("=value\n").is_err());
+    }
+
+    #[test]
+    fn format_attributes() {
+        let request = attributes(&[("protocol", "https"), ("host", "github.com")]);
+        assert_eq!(format(&request), "protocol=https\nhost=github.com\n");
+        assert_eq!(parse(&format(&request)), Ok(request));
+        assert_eq!(format(&[]), "");
+    }
+
+    #[test]
+    fn redact_secrets() {
+        assert_eq!(
+            redact(&attributes(&[
+                ("username", "me"),
+                ("password", "secret"),
+                ("oauth_refresh_token", "token"),
+            ])),
+            attributes(&[
+                ("username", "me"),
+                ("password", "<redacted>"),
+                ("oauth_refresh_token", "<redacted>"),
+            ])
+        );
+    }
+
+    #[test]
+    fn helper_config_value() {
+        assert_eq!(
+            helper_config("/mnt/c/Program Files/wslgit/wslgit.exe"),
+            "!'/mnt/c/Program Files/wslgit/wslgit.exe' --credential-helper"
+        );
+    }
+
+    #[test]
+    fn unknown_actions_are_ignored() {
+        let mut output = Vec::new();
+        assert_eq!(
+            relay(
+                "wslgit-no-helper.exe",
+                "capability",
+                &mut &b"host=a\n"[..],
+                &mut output
+            ),
+            Ok((Vec::new(), Vec::new()))
+        );
+        assert!(output.is_empty());
+        assert!(relay(
+            "wslgit-no-helper.exe",
+            "get",
+            &mut &b"host=a\n"[..],
+            &mut output
+        )
+        .is_err());
+    }
+}
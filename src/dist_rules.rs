@@ -0,0 +1,213 @@
+use std::fs;
+
+/// Selects the WSL distribution for working directories matching `pattern`.
+///
+/// The pattern is a Windows path, e.g. `D:\work` or `\\server\share\repos`,
+/// that matches the directory itself and everything below it. It may contain
+/// the wildcards `?` (any character), `*` (any characters within one path
+/// component) and `**` (any characters, including `\`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistRule {
+    pub pattern: String,
+    pub dist: String,
+}
+
+/// Normalize a Windows path for matching: `\` as separator, lower case and
+/// without trailing separators, e.g. `D:\` becomes `d:`.
+fn normalize(path: &str) -> Vec<char> {
+    path.replace('/', "\\")
+        .to_lowercase()
+        .trim_end_matches('\\')
+        .chars()
+        .collect()
+}
+
+/// Match `path` against the glob `pattern`, both already normalized.
+fn glob_match(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            (0..=path.len()).any(|i| glob_match(&pattern[2..], &path[i..]))
+        }
+        Some('*') => {
+            let component_end = path.iter().position(|&c| c == '\\').unwrap_or(path.len());
+            (0..=component_end).any(|i| glob_match(&pattern[1..], &path[i..]))
+        }
+        Some('?') => !path.is_empty() && path[0] != '\\' && glob_match(&pattern[1..], &path[1..]),
+        Some(&c) => path.first() == Some(&c) && glob_match(&pattern[1..], &path[1..]),
+    }
+}
+
+impl DistRule {
+    /// Return `true` if `path` or one of its parent directories matches the
+    /// pattern. Paths are compared case-insensitively and `/` is the same as
+    /// `\`.
+    pub fn matches(&self, path: &str) -> bool {
+        let pattern = normalize(&self.pattern);
+        let path = normalize(path);
+        if pattern.is_empty() {
+            return false;
+        }
+        // Try the path and each of its parents
+        (1..=path.len())
+            .filter(|&end| end == path.len() || path[end] == '\\')
+            .any(|end| glob_match(&pattern, &path[..end]))
+    }
+}
+
+/// Parse rules from a list like `D:\work=Ubuntu;C:\src\*\legacy=Debian`.
+pub fn parse_rules(rules: &str) -> Result<Vec<DistRule>, String> {
+    rules
+        .split(';')
+        .map(|rule| rule.trim())
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| match rule.rsplit_once('=') {
+            Some((pattern, dist)) if !pattern.trim().is_empty() && !dist.trim().is_empty() => {
+                Ok(DistRule {
+                    pattern: pattern.trim().to_string(),
+                    dist: dist.trim().to_string(),
+                })
+            }
+            _ => Err(format!(
+                "invalid rule `{}`, expected `<path>=<distribution>`",
+                rule
+            )),
+        })
+        .collect()
+}
+
+/// Return the alternative forms of the Windows path `path` that rules are
+/// matched against: the path itself and, for mapped network drives, the UNC
+/// path of the share.
+pub fn path_forms(path: &str) -> Vec<String> {
+    let mut forms = vec![path.to_string()];
+    if let Ok(resolved) = fs::canonicalize(path) {
+        let resolved = resolved.to_string_lossy().into_owned();
+        let resolved = if let Some(unc) = resolved.strip_prefix(r"\\?\UNC\") {
+            format!(r"\\{}", unc)
+        } else if let Some(local) = resolved.strip_prefix(r"\\?\") {
+            local.to_string()
+        } else {
+            resolved
+        };
+        if !forms.contains(&resolved) {
+            forms.push(resolved);
+        }
+    }
+    forms
+}
+
+/// Find the first rule that matches any of the `paths`.
+pub fn find_rule<'a>(rules: &'a [DistRule], paths: &[String]) -> Option<&'a DistRule> {
+    rules
+        .iter()
+        .find(|rule| paths.iter().any(|path| rule.matches(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, dist: &str) -> DistRule {
+        DistRule {
+            pattern: pattern.to_string(),
+            dist: dist.to_string(),
+        }
+    }
+
+    #[test]
+    fn prefix_rules() {
+        let cases: &[(&str, &str, bool)] = &[
+            (r"D:\work", r"D:\work", true),
+            (r"D:\work", r"D:\work\repo", true),
+            (r"D:\work\", r"D:\work\repo\src", true),
+            (r"D:\work", r"d:\WORK\Repo", true),
+            (r"D:/work", r"D:\work\repo", true),
+            (r"D:\work", r"D:/work/repo", true),
+            (r"D:\work", r"D:\workshop", false),
+            (r"D:\work", r"D:\", false),
+            (r"D:\work", r"C:\work", false),
+            (r"D:\", r"D:\work", true),
+            (r"\\server\share", r"\\server\share\repo", true),
+            (r"\\server\share", r"\\server\shared", false),
+            ("", r"D:\work", false),
+        ];
+        for &(pattern, path, expected) in cases {
+            assert_eq!(
+                rule(pattern, "dist").matches(path),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn glob_rules() {
+        let cases: &[(&str, &str, bool)] = &[
+            (r"C:\src\*\legacy", r"C:\src\a\legacy", true),
+            (r"C:\src\*\legacy", r"C:\src\a\legacy\sub", true),
+            (r"C:\src\*\legacy", r"C:\src\a\b\legacy", false),
+            (r"C:\src\**\legacy", r"C:\src\a\b\legacy", true),
+            (r"C:\src\**\legacy", r"C:\src\legacy", false),
+            (r"C:\src\repo-?", r"C:\src\repo-1\x", true),
+            (r"C:\src\repo-?", r"C:\src\repo-12", false),
+            (r"*:\work", r"E:\work\repo", true),
+            (r"C:\src\*", r"C:\src", false),
+            (r"C:\src\*", r"C:\src\anything", true),
+        ];
+        for &(pattern, path, expected) in cases {
+            assert_eq!(
+                rule(pattern, "dist").matches(path),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = vec![
+            rule(r"D:\work\new", "Ubuntu-22.04"),
+            rule(r"D:\work", "Ubuntu-WSL1"),
+            rule(r"\\server\share", "Debian"),
+            rule(r"D:\**", "Alpine"),
+        ];
+        let find = |paths: &[&str]| {
+            let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+            find_rule(&rules, &paths).map(|rule| rule.dist.as_str())
+        };
+        assert_eq!(find(&[r"D:\work\new\repo"]), Some("Ubuntu-22.04"));
+        assert_eq!(find(&[r"D:\work\old\repo"]), Some("Ubuntu-WSL1"));
+        assert_eq!(find(&[r"D:\other"]), Some("Alpine"));
+        assert_eq!(find(&[r"C:\other"]), None);
+        // A mapped drive matches with its UNC path
+        assert_eq!(find(&[r"Z:\repo", r"\\server\share\repo"]), Some("Debian"));
+    }
+
+    #[test]
+    fn parse_rule_list() {
+        assert_eq!(
+            parse_rules(r"D:\work=Ubuntu-WSL1; C:\src\*\legacy = Debian;"),
+            Ok(vec![
+                rule(r"D:\work", "Ubuntu-WSL1"),
+                rule(r"C:\src\*\legacy", "Debian")
+            ])
+        );
+        assert_eq!(parse_rules(""), Ok(vec![]));
+        assert!(parse_rules(r"D:\work").is_err());
+        assert!(parse_rules(r"D:\work=").is_err());
+        assert!(parse_rules("=Ubuntu").is_err());
+    }
+
+    #[test]
+    fn path_forms_of_missing_directory() {
+        assert_eq!(
+            path_forms(r"Q:\wslgit\does\not\exist"),
+            vec![r"Q:\wslgit\does\not\exist".to_string()]
+        );
+    }
+}
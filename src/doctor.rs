@@ -0,0 +1,391 @@
+use std::fmt::Write;
+use std::process::{Command, Output};
+
+use wsl::{self, Distribution};
+
+/// Marker printed by the interactive shell check, any other output comes from
+/// the startup scripts of the shell.
+const SHELL_MARKER: &str = "wslgit-doctor-marker";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        }
+    }
+}
+
+/// The result of a single diagnostic check.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// How to fix the problem, if the check did not pass.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Check {
+        Check {
+            name,
+            status: Status::Pass,
+            detail,
+            hint: None,
+        }
+    }
+
+    fn problem(name: &'static str, status: Status, detail: String, hint: &str) -> Check {
+        Check {
+            name,
+            status,
+            detail,
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+/// What wslgit would do for the current directory, as determined by `main`.
+pub struct Environment<'a> {
+    /// The WSL distribution git runs in, `None` for the default distribution.
+    pub dist: Option<&'a str>,
+    /// Why this distribution was chosen.
+    pub dist_reason: String,
+    /// The installed distributions, if they can be determined.
+    pub installed: Option<Vec<Distribution>>,
+    /// Path of bash inside WSL.
+    pub bash: &'a str,
+    /// Whether `fetch` or `push` would run in an interactive shell.
+    pub interactive_shell: bool,
+}
+
+/// Run `args` in WSL with the distribution of `env`.
+fn run_in_wsl(env: &Environment, args: &[&str]) -> std::io::Result<Output> {
+    let mut cmd = Command::new("wsl");
+    if let Some(dist) = env.dist {
+        cmd.arg("--distribution").arg(dist);
+    }
+    cmd.arg("-e").args(args).output()
+}
+
+fn first_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+fn check_distribution(env: &Environment) -> Check {
+    const NAME: &str = "distribution";
+    let detail = match env.dist {
+        Some(dist) => format!("{} ({})", dist, env.dist_reason),
+        None => env.dist_reason.clone(),
+    };
+    match (env.dist, env.installed.as_ref()) {
+        (Some(dist), Some(installed)) if wsl::find_distribution(installed, dist).is_none() => {
+            let names: Vec<&str> = installed.iter().map(|d| d.name.as_str()).collect();
+            Check::problem(
+                NAME,
+                Status::Fail,
+                format!("{} is not installed", detail),
+                &format!(
+                    "Use one of the installed distributions: {}",
+                    names.join(", ")
+                ),
+            )
+        }
+        _ => Check::pass(NAME, detail),
+    }
+}
+
+fn check_wsl(env: &Environment) -> Check {
+    const NAME: &str = "wsl";
+    let dist = env.dist.unwrap_or("default distribution");
+    match run_in_wsl(env, &["true"]) {
+        Ok(ref output) if output.status.success() => {
+            Check::pass(NAME, format!("wsl.exe can start {}", dist))
+        }
+        Ok(ref output) => Check::problem(
+            NAME,
+            Status::Fail,
+            format!(
+                "wsl.exe failed to start {}: {}",
+                dist,
+                first_line(&output.stderr)
+            ),
+            "Check that the distribution is installed with `wsl --list --verbose`, \
+             or set `default_dist` to an installed distribution.",
+        ),
+        Err(e) => Check::problem(
+            NAME,
+            Status::Fail,
+            format!("wsl.exe could not be executed: {}", e),
+            "Install WSL and make sure that wsl.exe is on the PATH.",
+        ),
+    }
+}
+
+fn check_git(env: &Environment) -> Check {
+    const NAME: &str = "git";
+    match run_in_wsl(env, &["git", "--version"]) {
+        Ok(ref output) if output.status.success() => Check::pass(NAME, first_line(&output.stdout)),
+        _ => Check::problem(
+            NAME,
+            Status::Fail,
+            "git could not be executed inside WSL".to_string(),
+            "Install git inside the WSL distribution, e.g. `sudo apt install git`.",
+        ),
+    }
+}
+
+/// Check the output of the interactive shell check, which must be exactly
+/// the marker.
+fn check_shell_output(output: &[u8]) -> Check {
+    const NAME: &str = "interactive shell";
+    let output = String::from_utf8_lossy(output);
+    if output == SHELL_MARKER {
+        return Check::pass(NAME, "bash -i prints no additional output".to_string());
+    }
+    if !output.contains(SHELL_MARKER) {
+        return Check::problem(
+            NAME,
+            Status::Fail,
+            format!(
+                "bash -i does not print the output of git: {:?}",
+                output.trim()
+            ),
+            "Make sure that .bashrc does not exit or exec another program, or set \
+             WSLGIT_USE_INTERACTIVE_SHELL=false.",
+        );
+    }
+    let extra: String = output.replacen(SHELL_MARKER, "", 1);
+    Check::problem(
+        NAME,
+        Status::Warn,
+        format!("bash -i prints additional output: {:?}", extra.trim()),
+        "The output is stripped automatically when the output of wslgit is not \
+         written to a terminal. Remove output from .bashrc when running for \
+         wslgit to keep it off the terminal, e.g. skip it if the WSLGIT \
+         variable is set.",
+    )
+}
+
+fn check_interactive_shell(env: &Environment) -> Check {
+    const NAME: &str = "interactive shell";
+    if !env.interactive_shell {
+        return Check::pass(NAME, "not used".to_string());
+    }
+    let script = format!("printf '%s' {}", SHELL_MARKER);
+    match run_in_wsl(env, &[env.bash, "-ic", &script]) {
+        Ok(ref output) if output.status.success() => check_shell_output(&output.stdout),
+        _ => Check::problem(
+            NAME,
+            Status::Fail,
+            format!("{} -i could not be executed", env.bash),
+            "Install bash inside the WSL distribution, or set \
+             WSLGIT_USE_INTERACTIVE_SHELL=false.",
+        ),
+    }
+}
+
+fn check_wslpath(env: &Environment) -> Check {
+    const NAME: &str = "wslpath";
+    match run_in_wsl(env, &["wslpath", "-w", "/"]) {
+        Ok(ref output) if output.status.success() && !output.stdout.is_empty() => {
+            Check::pass(NAME, format!("/ is {}", first_line(&output.stdout)))
+        }
+        _ => Check::problem(
+            NAME,
+            Status::Warn,
+            "wslpath could not translate /".to_string(),
+            "Paths that wslgit cannot translate itself are passed on unchanged. \
+             Update WSL to get a working wslpath.",
+        ),
+    }
+}
+
+/// Run all checks in order. Checks that need a running distribution are
+/// skipped if it cannot be started.
+pub fn run(env: &Environment) -> Vec<Check> {
+    let mut checks = vec![check_distribution(env)];
+    if checks[0].status == Status::Fail {
+        return checks;
+    }
+    checks.push(check_wsl(env));
+    if checks[1].status == Status::Fail {
+        return checks;
+    }
+    checks.push(check_git(env));
+    checks.push(check_interactive_shell(env));
+    checks.push(check_wslpath(env));
+    checks
+}
+
+/// Return `true` if none of the checks failed.
+pub fn passed(checks: &[Check]) -> bool {
+    checks.iter().all(|check| check.status != Status::Fail)
+}
+
+/// Format the checks as a human readable report.
+pub fn report(checks: &[Check]) -> String {
+    let mut report = String::new();
+    for check in checks {
+        let status = match check.status {
+            Status::Pass => "[ OK ]",
+            Status::Warn => "[WARN]",
+            Status::Fail => "[FAIL]",
+        };
+        writeln!(report, "{} {}: {}", status, check.name, check.detail).unwrap();
+        if let Some(ref hint) = check.hint {
+            writeln!(report, "       {}", hint).unwrap();
+        }
+    }
+    report
+}
+
+/// Quote `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Format the checks as JSON, e.g.
+/// `{"ok":true,"checks":[{"name":"git","status":"pass","detail":"...","hint":null}]}`.
+pub fn report_json(checks: &[Check]) -> String {
+    let entries: Vec<String> = checks
+        .iter()
+        .map(|check| {
+            format!(
+                "{{\"name\":{},\"status\":{},\"detail\":{},\"hint\":{}}}",
+                json_string(check.name),
+                json_string(check.status.as_str()),
+                json_string(&check.detail),
+                check
+                    .hint
+                    .as_ref()
+                    .map_or("null".to_string(), |hint| json_string(hint))
+            )
+        })
+        .collect();
+    format!(
+        "{{\"ok\":{},\"checks\":[{}]}}",
+        passed(checks),
+        entries.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks() -> Vec<Check> {
+        vec![
+            Check::pass("git", "git version 2.39.2".to_string()),
+            Check::problem(
+                "interactive shell",
+                Status::Fail,
+                "bash -i prints \"hello\"".to_string(),
+                "Fix .bashrc",
+            ),
+        ]
+    }
+
+    #[test]
+    fn distribution() {
+        let mut env = Environment {
+            dist: Some("debian"),
+            dist_reason: "default_dist from default".to_string(),
+            installed: Some(wsl::parse_distributions(
+                "NAME STATE VERSION\n* Ubuntu Running 2\n  Debian Stopped 1\n",
+            )),
+            bash: "/bin/bash",
+            interactive_shell: false,
+        };
+        assert_eq!(check_distribution(&env).status, Status::Pass);
+
+        env.dist = Some("Alpine");
+        let check = check_distribution(&env);
+        assert_eq!(check.status, Status::Fail);
+        assert_eq!(
+            check.hint,
+            Some("Use one of the installed distributions: Ubuntu, Debian".to_string())
+        );
+
+        env.installed = None;
+        assert_eq!(check_distribution(&env).status, Status::Pass);
+    }
+
+    #[test]
+    fn shell_output() {
+        assert_eq!(
+            check_shell_output(SHELL_MARKER.as_bytes()).status,
+            Status::Pass
+        );
+
+        let check = check_shell_output(format!("Welcome!\n{}", SHELL_MARKER).as_bytes());
+        assert_eq!(check.status, Status::Warn);
+        assert_eq!(
+            check.detail,
+            "bash -i prints additional output: \"Welcome!\""
+        );
+        assert!(check.hint.is_some());
+
+        assert_eq!(check_shell_output(b"").status, Status::Fail);
+    }
+
+    #[test]
+    fn text_report() {
+        assert_eq!(
+            report(&checks()),
+            "[ OK ] git: git version 2.39.2\n\
+             [FAIL] interactive shell: bash -i prints \"hello\"\n\
+             \x20      Fix .bashrc\n"
+        );
+        assert!(!passed(&checks()));
+        assert!(passed(&checks()[..1]));
+    }
+
+    #[test]
+    fn json_report() {
+        assert_eq!(
+            report_json(&checks()),
+            "{\"ok\":false,\"checks\":[\
+             {\"name\":\"git\",\"status\":\"pass\",\"detail\":\"git version 2.39.2\",\"hint\":null},\
+             {\"name\":\"interactive shell\",\"status\":\"fail\",\
+             \"detail\":\"bash -i prints \\\"hello\\\"\",\"hint\":\"Fix .bashrc\"}]}"
+        );
+        assert_eq!(report_json(&[]), "{\"ok\":true,\"checks\":[]}");
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(
+            json_string("a\"b\\c\nd\u{1}é"),
+            "\"a\\\"b\\\\c\\nd\\u0001é\""
+        );
+    }
+}
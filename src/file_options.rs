@@ -0,0 +1,234 @@
+use std::fs;
+
+use bridge;
+use config::DistBridge;
+use translate::PathTranslator;
+
+/// Options of git subcommands whose value is a file that git only reads.
+const FILE_OPTIONS: &[(&str, &[&str])] = &[
+    (
+        "commit",
+        &["-F", "--file", "-t", "--template", "--pathspec-from-file"],
+    ),
+    ("tag", &["-F", "--file"]),
+    ("merge", &["-F", "--file"]),
+    ("add", &["--pathspec-from-file"]),
+    ("checkout", &["--pathspec-from-file"]),
+    ("reset", &["--pathspec-from-file"]),
+    ("restore", &["--pathspec-from-file"]),
+    ("rm", &["--pathspec-from-file"]),
+];
+
+/// Options that read from stdin if their value is `-`.
+const STDIN_OPTIONS: &[&str] = &["-F", "--file", "--pathspec-from-file"];
+
+/// The value of a file option in the arguments of a subcommand.
+#[derive(Debug, PartialEq)]
+pub struct FileOption {
+    /// Index of the argument that contains the path.
+    pub index: usize,
+    /// The text before the path in that argument, e.g. `--file=` or `-F`.
+    pub prefix: String,
+    pub path: String,
+    /// Whether the option reads from stdin if the path is `-`.
+    pub accepts_stdin: bool,
+}
+
+/// How the file of a file option is made available to git.
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    /// Git can read the file itself.
+    Unchanged,
+    /// The file is in another distribution and bridged as configured with
+    /// `dist_bridge`.
+    Bridge,
+    /// The file is copied into the distribution.
+    Copy,
+    /// The file is passed on stdin.
+    Stdin,
+}
+
+/// Find the file options in `args`, the arguments of `subcommand` before
+/// `--`.
+pub fn find(subcommand: &str, args: &[String]) -> Vec<FileOption> {
+    let options = match FILE_OPTIONS.iter().find(|&&(cmd, _)| cmd == subcommand) {
+        Some(&(_, options)) => options,
+        None => return Vec::new(),
+    };
+    let file_option = |option: &str, index: usize, prefix: &str, path: &str| FileOption {
+        index,
+        prefix: prefix.to_string(),
+        path: path.to_string(),
+        accepts_stdin: STDIN_OPTIONS.contains(&option),
+    };
+
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        for &option in options {
+            if arg == option {
+                if let Some(path) = args.get(i + 1) {
+                    found.push(file_option(option, i + 1, "", path));
+                    i += 1;
+                }
+                break;
+            }
+            let prefix = if option.starts_with("--") {
+                format!("{}=", option)
+            } else {
+                option.to_string()
+            };
+            if let Some(path) = arg.strip_prefix(prefix.as_str()) {
+                found.push(file_option(option, i, &prefix, path));
+                break;
+            }
+        }
+        i += 1;
+    }
+    found
+}
+
+/// Decide how the file of `option` is made available to git.
+///
+/// * `reachable` - Whether git can access the path itself.
+/// * `other_dist` - Whether the path is in another distribution than the one
+///   git runs in.
+/// * `dist_known` - Whether the distribution git runs in is known, so that
+///   files can be copied into it.
+/// * `stdin_used` - Whether another option already uses stdin.
+pub fn target(
+    option: &FileOption,
+    reachable: bool,
+    other_dist: bool,
+    dist_known: bool,
+    stdin_used: bool,
+) -> Result<Target, String> {
+    if reachable {
+        Ok(Target::Unchanged)
+    } else if other_dist {
+        Ok(Target::Bridge)
+    } else if dist_known {
+        Ok(Target::Copy)
+    } else if option.accepts_stdin && !stdin_used {
+        Ok(Target::Stdin)
+    } else {
+        Err(format!("{} cannot be accessed from WSL", option.path))
+    }
+}
+
+/// Make the files of file options in `args`, the arguments of `subcommand`,
+/// available to git if it cannot access them, by replacing their paths with
+/// a copy inside WSL or with `-` for stdin. Files in another distribution
+/// are bridged as configured with `mode`.
+///
+/// Returns the contents to write to the stdin of git, if any.
+pub fn bridge_files(
+    subcommand: &str,
+    args: &mut [String],
+    translator: &PathTranslator,
+    mode: DistBridge,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut stdin = None;
+    for option in find(subcommand, args) {
+        let target = target(
+            &option,
+            translator.is_reachable(&option.path),
+            translator.check_dist(&option.path).is_err(),
+            translator.dist_share().is_some(),
+            stdin.is_some(),
+        )?;
+        let path = match target {
+            Target::Unchanged => continue,
+            Target::Bridge => bridge::bridge(&option.path, translator, mode)?,
+            Target::Copy => bridge::copy_into_dist(&option.path, translator)?,
+            Target::Stdin => {
+                let contents = fs::read(&option.path)
+                    .map_err(|e| format!("failed to read {}: {}", option.path, e))?;
+                stdin = Some(contents);
+                "-".to_string()
+            }
+        };
+        args[option.index] = format!("{}{}", option.prefix, path);
+    }
+    Ok(stdin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn option(index: usize, prefix: &str, path: &str, accepts_stdin: bool) -> FileOption {
+        FileOption {
+            index,
+            prefix: prefix.to_string(),
+            path: path.to_string(),
+            accepts_stdin,
+        }
+    }
+
+    #[test]
+    fn find_file_options() {
+        let args = strings(&[
+            "-a",
+            "-F",
+            r"C:\Temp\msg",
+            r"--template=D:\template",
+            "--pathspec-from-file",
+            r"D:\paths",
+        ]);
+        assert_eq!(
+            find("commit", &args),
+            vec![
+                option(2, "", r"C:\Temp\msg", true),
+                option(3, "--template=", r"D:\template", false),
+                option(5, "", r"D:\paths", true),
+            ]
+        );
+        assert_eq!(
+            find("tag", &strings(&["-a", r"-FC:\msg", "v1"])),
+            vec![option(1, "-F", r"C:\msg", true)]
+        );
+        assert_eq!(
+            find("commit", &strings(&[r"--file=C:\msg"])),
+            vec![option(0, "--file=", r"C:\msg", true)]
+        );
+        // Only options of the subcommand
+        assert_eq!(find("config", &strings(&["--file", r"C:\config"])), vec![]);
+        assert_eq!(find("tag", &strings(&["-t", r"C:\template"])), vec![]);
+        // A missing value
+        assert_eq!(find("commit", &strings(&["-F"])), vec![]);
+    }
+
+    #[test]
+    fn choose_target() {
+        let message = option(1, "", r"D:\msg", true);
+        let template = option(1, "--template=", r"D:\template", false);
+        assert_eq!(
+            target(&message, true, false, false, false),
+            Ok(Target::Unchanged)
+        );
+        assert_eq!(
+            target(&message, true, true, true, false),
+            Ok(Target::Unchanged)
+        );
+        assert_eq!(
+            target(&message, false, true, true, true),
+            Ok(Target::Bridge)
+        );
+        assert_eq!(target(&message, false, false, true, true), Ok(Target::Copy));
+        assert_eq!(
+            target(&message, false, false, false, false),
+            Ok(Target::Stdin)
+        );
+        assert!(target(&message, false, false, false, true).is_err());
+        assert_eq!(
+            target(&template, false, false, false, false),
+            Err(r"D:\template cannot be accessed from WSL".to_string())
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+
+/// Flags of `WSLENV` entries: `p` translates a path, `l` a list of paths,
+/// `u` shares the variable only from Windows to WSL and `w` only from WSL to
+/// Windows.
+const FLAGS: &str = "pluw";
+
+/// Forwarded by default, with the path-valued variables of git translated.
+/// Later rules take precedence.
+pub const DEFAULT_RULES: &[&str] = &[
+    "GIT_*",
+    "GIT_DIR/p",
+    "GIT_WORK_TREE/p",
+    "GIT_INDEX_FILE/p",
+    "GIT_OBJECT_DIRECTORY/p",
+    "GIT_COMMON_DIR/p",
+    "GIT_CONFIG_GLOBAL/p",
+    "GIT_CONFIG_SYSTEM/p",
+    "GIT_TEMPLATE_DIR/p",
+    "GIT_ALTERNATE_OBJECT_DIRECTORIES/l",
+    "GIT_CEILING_DIRECTORIES/l",
+];
+
+/// Only forwarded by a rule with exactly their name, not by a pattern: the
+/// Windows git installation, and the programs git runs, which are usually
+/// Windows programs that git inside WSL cannot run. wslgit bridges the askpass
+/// program itself.
+const NOT_FORWARDED: &[&str] = &[
+    "GIT_EXEC_PATH",
+    "GIT_ASKPASS",
+    "GIT_SSH",
+    "GIT_SSH_COMMAND",
+    "GIT_EDITOR",
+    "GIT_SEQUENCE_EDITOR",
+];
+
+/// Forwards the Windows environment variables whose names match `pattern`
+/// into WSL, with the `WSLENV` `flags`.
+///
+/// The pattern is a variable name that may contain the wildcard `*` (any
+/// characters). Names are compared case-insensitively.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardRule {
+    pub pattern: String,
+    pub flags: String,
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(b'*') => (0..=name.len()).any(|i| glob_match(&pattern[1..], &name[i..])),
+        Some(c) => {
+            name.first().is_some_and(|n| n.eq_ignore_ascii_case(c))
+                && glob_match(&pattern[1..], &name[1..])
+        }
+    }
+}
+
+impl ForwardRule {
+    /// Parse a rule like `GIT_DIR/p` or `GIT_*`, in the syntax of `WSLENV`.
+    pub fn parse(rule: &str) -> Result<ForwardRule, String> {
+        let (pattern, flags) = rule.trim().split_once('/').unwrap_or((rule.trim(), ""));
+        let valid_flags = flags.chars().all(|c| FLAGS.contains(c))
+            && !(flags.contains('p') && flags.contains('l'));
+        if pattern.is_empty() || pattern.contains(':') || !valid_flags {
+            return Err(format!(
+                "invalid variable `{}`, expected `<name>` or `<name>/<flags>` with flags of p, l, u and w",
+                rule
+            ));
+        }
+        Ok(ForwardRule {
+            pattern: pattern.to_string(),
+            flags: flags.to_string(),
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        glob_match(self.pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+impl fmt::Display for ForwardRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.flags.is_empty() {
+            write!(f, "{}", self.pattern)
+        } else {
+            write!(f, "{}/{}", self.pattern, self.flags)
+        }
+    }
+}
+
+/// The rules that are used if none are configured.
+pub fn default_rules() -> Vec<ForwardRule> {
+    DEFAULT_RULES
+        .iter()
+        .map(|rule| ForwardRule::parse(rule).unwrap())
+        .collect()
+}
+
+/// Select the variables of `names` to forward and the flags of each, from the
+/// last rule that matches it, see [`NOT_FORWARDED`] for the exceptions.
+pub fn select<I: IntoIterator<Item = String>>(
+    rules: &[ForwardRule],
+    names: I,
+) -> Vec<(String, String)> {
+    let mut selected: Vec<(String, String)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let excluded = NOT_FORWARDED
+                .iter()
+                .any(|excluded| excluded.eq_ignore_ascii_case(&name));
+            let rule = rules
+                .iter()
+                .rev()
+                .filter(|rule| !excluded || rule.pattern.eq_ignore_ascii_case(&name))
+                .find(|rule| rule.matches(&name))?;
+            Some((name, rule.flags.clone()))
+        })
+        .collect();
+    selected.sort();
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_rules() {
+        let cases: &[(&str, (&str, &str))] = &[
+            ("GIT_*", ("GIT_*", "")),
+            ("GIT_DIR/p", ("GIT_DIR", "p")),
+            (
+                " GIT_CEILING_DIRECTORIES/l ",
+                ("GIT_CEILING_DIRECTORIES", "l"),
+            ),
+            ("GIT_TRACE/u", ("GIT_TRACE", "u")),
+            ("EDITOR/pu", ("EDITOR", "pu")),
+        ];
+        for &(rule, (pattern, flags)) in cases {
+            let parsed = ForwardRule::parse(rule).unwrap();
+            assert_eq!(
+                parsed,
+                ForwardRule {
+                    pattern: pattern.to_string(),
+                    flags: flags.to_string()
+                },
+                "{}",
+                rule
+            );
+            assert_eq!(parsed.to_string(), rule.trim());
+        }
+        for &rule in &["", "/p", "GIT_DIR/x", "GIT_DIR/pl", "A:B"] {
+            assert!(ForwardRule::parse(rule).is_err(), "{}", rule);
+        }
+        assert_eq!(default_rules().len(), DEFAULT_RULES.len());
+    }
+
+    #[test]
+    fn match_names() {
+        let git = ForwardRule::parse("GIT_*").unwrap();
+        assert!(git.matches("GIT_TRACE"));
+        assert!(git.matches("git_author_name"));
+        assert!(!git.matches("WSLGIT"));
+        assert!(!git.matches("GITHUB_TOKEN"));
+        assert!(ForwardRule::parse("*_EDITOR")
+            .unwrap()
+            .matches("GIT_EDITOR"));
+    }
+
+    #[test]
+    fn select_variables() {
+        let env = names(&[
+            "PATH",
+            "GIT_AUTHOR_NAME",
+            "GIT_DIR",
+            "GIT_CEILING_DIRECTORIES",
+            "GIT_EXEC_PATH",
+            "GIT_ASKPASS",
+            "GIT_SSH",
+            "WSLGIT",
+        ]);
+        assert_eq!(
+            select(&default_rules(), env.clone()),
+            vec![
+                ("GIT_AUTHOR_NAME".to_string(), "".to_string()),
+                ("GIT_CEILING_DIRECTORIES".to_string(), "l".to_string()),
+                ("GIT_DIR".to_string(), "p".to_string()),
+            ]
+        );
+        // Later rules take precedence
+        let rules = vec![
+            ForwardRule::parse("GIT_DIR/p").unwrap(),
+            ForwardRule::parse("GIT_*/u").unwrap(),
+        ];
+        assert_eq!(
+            select(&rules, names(&["GIT_DIR"])),
+            vec![("GIT_DIR".to_string(), "u".to_string())]
+        );
+        // The exceptions only by their name
+        let exceptions = [
+            "GIT_EXEC_PATH",
+            "GIT_ASKPASS",
+            "GIT_SSH",
+            "GIT_SSH_COMMAND",
+            "GIT_EDITOR",
+            "GIT_SEQUENCE_EDITOR",
+        ];
+        for &name in &exceptions {
+            assert_eq!(select(&default_rules(), names(&[name])), vec![], "{}", name);
+            let rules = vec![
+                ForwardRule::parse(&format!("{}/p", name)).unwrap(),
+                ForwardRule::parse("GIT_*/u").unwrap(),
+            ];
+            assert_eq!(
+                select(&rules, names(&[name])),
+                vec![(name.to_string(), "p".to_string())],
+                "{}",
+                name
+            );
+        }
+        assert_eq!(select(&[], env), vec![]);
+    }
+}
@@ -0,0 +1,209 @@
+/// Global options of git that expect a value, either in the next argument or
+/// after a `=`.
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-C",
+    "-c",
+    "--git-dir",
+    "--work-tree",
+    "--namespace",
+    "--config-env",
+    "--super-prefix",
+    "--list-cmds",
+    "--attr-source",
+];
+
+/// A git command line, split into the global options, the subcommand, its
+/// arguments and the pathspec after `--`.
+///
+/// Global options without a value (e.g. `-P`, `--no-pager`, `--bare`,
+/// `--literal-pathspecs` or `--exec-path`) are skipped.
+#[derive(Debug, Default, PartialEq)]
+pub struct Invocation {
+    /// All global options before the subcommand, with their values.
+    pub global_options: Vec<String>,
+    /// Paths given with `-C`, in order.
+    pub directories: Vec<String>,
+    /// Configuration given with `-c`, e.g. `user.name=Me`.
+    pub config: Vec<String>,
+    /// Path given with `--git-dir`.
+    pub git_dir: Option<String>,
+    /// Path given with `--work-tree`.
+    pub work_tree: Option<String>,
+    /// The git subcommand, e.g. `commit`, or `None` for e.g. `git --version`.
+    pub subcommand: Option<String>,
+    /// Arguments of the subcommand before `--`.
+    pub args: Vec<String>,
+    /// Arguments after `--`.
+    pub pathspec: Vec<String>,
+}
+
+impl Invocation {
+    /// Parse the arguments of a git command line, without the `git` itself.
+    pub fn parse(args: &[String]) -> Invocation {
+        let mut invocation = Invocation::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                invocation.pathspec = args.cloned().collect();
+                return invocation;
+            } else if !arg.starts_with('-') {
+                invocation.subcommand = Some(arg.clone());
+                break;
+            }
+            invocation.global_options.push(arg.clone());
+
+            let (name, value) = match arg.find('=') {
+                Some(pos) if arg.starts_with("--") => {
+                    (&arg[..pos], Some(arg[pos + 1..].to_string()))
+                }
+                _ => (arg.as_str(), None),
+            };
+            if !OPTIONS_WITH_VALUE.contains(&name) {
+                continue;
+            }
+            let value = match value {
+                Some(value) => value,
+                None => match args.next() {
+                    Some(value) => {
+                        invocation.global_options.push(value.clone());
+                        value.clone()
+                    }
+                    None => break,
+                },
+            };
+            match name {
+                "-C" => invocation.directories.push(value),
+                "-c" => invocation.config.push(value),
+                "--git-dir" => invocation.git_dir = Some(value),
+                "--work-tree" => invocation.work_tree = Some(value),
+                _ => {}
+            }
+        }
+
+        for arg in args.by_ref() {
+            if arg == "--" {
+                break;
+            }
+            invocation.args.push(arg.clone());
+        }
+        invocation.pathspec = args.cloned().collect();
+        invocation
+    }
+
+    /// Return `true` if the subcommand is `subcommand`.
+    pub fn is(&self, subcommand: &str) -> bool {
+        self.subcommand.as_deref() == Some(subcommand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Invocation {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Invocation::parse(&args)
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn subcommand() {
+        let cases: &[(&[&str], Option<&str>)] = &[
+            (&["rev-parse", "--show-toplevel"], Some("rev-parse")),
+            (&["log", "--", "init"], Some("log")),
+            (&["log", "--grep", "push"], Some("log")),
+            (&["-C", "init", "status"], Some("status")),
+            (
+                &["-c", "remote.x=y", "--no-pager", "remote", "-v"],
+                Some("remote"),
+            ),
+            (
+                &["--git-dir", "remote", "--work-tree", "init", "log"],
+                Some("log"),
+            ),
+            (
+                &["--git-dir=/x", "--work-tree=/y", "worktree", "list"],
+                Some("worktree"),
+            ),
+            (&["--namespace", "init", "-P", "branch"], Some("branch")),
+            (
+                &["--namespace=init", "--literal-pathspecs", "add"],
+                Some("add"),
+            ),
+            (&["--exec-path", "status"], Some("status")),
+            (&["--exec-path=/usr/lib/git-core", "status"], Some("status")),
+            (
+                &["-p", "--bare", "--no-replace-objects", "fetch"],
+                Some("fetch"),
+            ),
+            (&["--version"], None),
+            (&["--", "init"], None),
+            (&["-C"], None),
+            (&[], None),
+        ];
+        for &(args, expected) in cases {
+            assert_eq!(parse(args).subcommand.as_deref(), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn global_options() {
+        let invocation = parse(&[
+            "-C",
+            "a",
+            "-c",
+            "user.name=Me",
+            "-C",
+            "b",
+            "--git-dir=.git",
+            "--work-tree",
+            "c",
+            "-c",
+            "core.pager=",
+            "status",
+            "-C",
+            "d",
+        ]);
+        assert_eq!(invocation.directories, strings(&["a", "b"]));
+        assert_eq!(invocation.config, strings(&["user.name=Me", "core.pager="]));
+        assert_eq!(invocation.git_dir, Some(".git".to_string()));
+        assert_eq!(invocation.work_tree, Some("c".to_string()));
+        assert_eq!(invocation.args, strings(&["-C", "d"]));
+        assert_eq!(
+            invocation.global_options,
+            strings(&[
+                "-C",
+                "a",
+                "-c",
+                "user.name=Me",
+                "-C",
+                "b",
+                "--git-dir=.git",
+                "--work-tree",
+                "c",
+                "-c",
+                "core.pager=",
+            ])
+        );
+    }
+
+    #[test]
+    fn arguments_and_pathspec() {
+        let invocation = parse(&["--no-pager", "log", "--oneline", "--", "init", "--"]);
+        assert!(invocation.is("log"));
+        assert!(!invocation.is("init"));
+        assert_eq!(invocation.args, strings(&["--oneline"]));
+        assert_eq!(invocation.pathspec, strings(&["init", "--"]));
+
+        let invocation = parse(&["status"]);
+        assert!(invocation.args.is_empty());
+        assert!(invocation.pathspec.is_empty());
+
+        let invocation = parse(&["--version"]);
+        assert!(!invocation.is("status"));
+    }
+}
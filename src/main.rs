@@ -1,25 +1,65 @@
 use std::env;
 
 use std::fs::OpenOptions;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[macro_use]
 extern crate lazy_static;
 extern crate regex;
-use regex::bytes::Regex;
+extern crate toml;
+use regex::bytes::{Captures, Regex};
 
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+
+mod alias;
+mod askpass;
+mod bridge;
+mod cache;
+mod config;
+mod credential;
+mod dist_rules;
+mod doctor;
+mod file_options;
 mod fork;
+mod forward_env;
+mod invocation;
+mod mounts;
+mod output;
+mod shell;
+mod ssh_agent;
+mod translate;
+mod unc;
 mod wsl;
 
+use config::{Config, ConfigError, DistBridge, InteractiveShell, Source};
+use invocation::Invocation;
+use mounts::MountTable;
+use translate::PathTranslator;
+use unc::UncError;
+
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 const BASH_EXECUTABLE: &str = "/bin/bash";
 
-static mut DOUBLE_DASH_FOUND: bool = false;
-
-fn translate_path_to_unix(argument: String) -> String {
+/// Translate the Windows paths in `argument` to WSL paths, bridging paths into
+/// another distribution as configured with `dist_bridge`.
+///
+/// * `pathspec` - Whether the argument is after `--`, where relative paths
+///   are translated even if they do not exist.
+///
+/// Returns an error if a path into another distribution cannot be bridged.
+fn translate_path_to_unix(
+    argument: String,
+    translator: &PathTranslator,
+    dist_bridge: DistBridge,
+    pathspec: bool,
+) -> Result<String, String> {
     let argument = argument.as_bytes();
 
     // An absolute or UNC path must:
@@ -50,17 +90,51 @@ fn translate_path_to_unix(argument: String) -> String {
     let has_file_prefix = argument.starts_with(b"file://");
     let has_transport_protocol_prefix = TRANSPORT_PROTOCOL_RE.is_match(argument);
 
+    // Translate the path natively if possible, otherwise use wslpath.
+    // The first error of bridging is kept, since the replacement cannot fail.
+    let mut error = None;
+    let replace_path = |caps: &Captures| -> Vec<u8> {
+        let path = std::str::from_utf8(&caps["path"]).unwrap();
+        let unix_path = match translator.to_unix(path) {
+            Some(unix_path) => unix_path,
+            // Paths into another distribution can only be bridged
+            None if translator.check_dist(path).is_err() => {
+                match bridge::bridge(path, translator, dist_bridge) {
+                    Ok(bridged) => {
+                        if enable_logging() {
+                            log(format!("{:?} bridged to {:?}", path, bridged));
+                        }
+                        bridged
+                    }
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        return caps[0].to_vec();
+                    }
+                }
+            }
+            None => {
+                wsl::wslpath(translator.dist_name(), "-u", path).unwrap_or_else(|| path.to_string())
+            }
+        };
+        let mut replacement = caps["pre"].to_vec();
+        replacement.extend_from_slice(unix_path.as_bytes());
+        replacement
+    };
+
     let argument = if !has_transport_protocol_prefix {
         ABS_WINPATH_RE
-            .replace_all(argument, &b"${pre}$(wslpath '${path}')"[..])
+            .replace_all(argument, replace_path)
             .into_owned()
     } else if has_file_prefix {
         FILE_ABS_WINPATH_RE
-            .replace_all(argument, &b"${pre}$(wslpath '${path}')"[..])
+            .replace_all(argument, replace_path)
             .into_owned()
     } else {
         argument.to_vec()
     };
+    if let Some(e) = error {
+        return Err(e);
+    }
 
     // Relative paths that needs to have their slashes changed must:
     // 1. Be at the beginning of the string, or after a whitespace, colon, or equal-sign.
@@ -79,12 +153,9 @@ fn translate_path_to_unix(argument: String) -> String {
         let path_cap = caps.name("path").unwrap();
         let path = std::str::from_utf8(&path_cap.as_bytes()).unwrap();
 
-        let double_dash_found = unsafe { DOUBLE_DASH_FOUND };
-
         // If the path in the argument exists then it is definitely a relative path,
         // or if the argument is after double-dashes then it is very likely a relative path.
-        let translate_relative_path =
-            has_file_prefix || double_dash_found || Path::new(path).exists();
+        let translate_relative_path = has_file_prefix || pathspec || Path::new(path).exists();
 
         if translate_relative_path {
             let wsl_path = path.replace("\\", "/");
@@ -98,237 +169,204 @@ fn translate_path_to_unix(argument: String) -> String {
                 None => "",
             };
 
-            return format!("{}{}{}", before, wsl_path, after);
+            return Ok(format!("{}{}{}", before, wsl_path, after));
         }
     }
 
-    std::str::from_utf8(&argument).unwrap().to_string()
+    Ok(std::str::from_utf8(&argument).unwrap().to_string())
 }
 
-fn translate_path_to_win(line: &[u8]) -> Vec<u8> {
+fn translate_path_to_win(path: &str, translator: &PathTranslator) -> String {
     // Windows can handle both / and \ as path separator so there is no need to convert relative paths.
-
-    // An absolute Unix path must:
-    // 1. Be at the beginning of the string or after a whitespace.
-    // 2. Begin with /
-    // 3. Not contain the characters: <>:|?'* or newline.
-    // Note that when an absolute path is found then the rest of the line is passed to wslpath as argument!
-    // The only exception to this is lines ending in ` (fetch)` or ` (push)`, as in the output of `git remote -v`.
-    lazy_static! {
-        static ref WSLPATH_RE: Regex =
-            Regex::new(r"(?m)(?P<pre>^|[[:space:]])(?P<path>/([^<>:|?'*\n]*/?)*)")
-                .expect("Failed to compile WSLPATH_RE regex");
-    }
-
-    if WSLPATH_RE.is_match(line) {
-        // Use wslpath to convert the path to a windows path.
-        let line = WSLPATH_RE
-            .replace_all(line, &b"${pre}$(wslpath -w '${path}')"[..])
-            .into_owned();
-
-        // Fixup output of `git remote -v`, i.e. lines ending in
-        // ` (fetch)` or ` (push)` - move remote types outside the wslpath call.
-        lazy_static! {
-            static ref REMOTE_FIX_RE: Regex =
-                Regex::new(r"(?m)\s(?P<type>(\(fetch\))|(\(push\)))'\)")
-                    .expect("Failed to compile REMOTE_FIX_RE regex");
-        }
-        let line = REMOTE_FIX_RE
-            .replace_all(&line, &b"') ${type}"[..])
-            .into_owned();
-        let line = std::str::from_utf8(&line).unwrap();
-
-        let echo_cmd = format!("echo -n \"{}\"", line);
-        let output = Command::new("wsl")
-            .arg("-e")
-            .arg(BASH_EXECUTABLE)
-            .arg("-c")
-            .arg(&echo_cmd)
-            .output()
-            .expect("failed to execute echo_cmd");
-        if enable_logging() {
-            log(format!(
-                "{:?} -> {} -> {:?}",
-                line,
-                echo_cmd,
-                std::str::from_utf8(&output.stdout).unwrap()
-            ));
-        }
-        return output.stdout;
-    }
-    line.to_vec()
-}
-
-fn escape_characters(arg: String) -> String {
-    arg.replace("\n", "$'\n'")
-        .replace("\"", "\\\"")
-        .replace("<", "\\<")
-        .replace(">", "\\>")
-        .replace("!", "\\!")
-}
-
-fn invalid_characters(ch: char) -> bool {
-    match ch {
-        ' ' | '(' | ')' | '|' => true,
-        _ => false,
+    // Translate the path natively if possible, otherwise use wslpath.
+    let win_path = translator
+        .to_win(path)
+        .or_else(|| wsl::wslpath(translator.dist_name(), "-w", path))
+        .unwrap_or_else(|| path.to_string());
+    if enable_logging() {
+        log(format!("{:?} -> {:?}", path, win_path));
     }
+    win_path
 }
 
-fn quote_argument(arg: String) -> String {
-    if arg.contains(invalid_characters) || arg.is_empty() {
-        return format!("\"{}\"", arg);
-    } else {
-        return arg;
-    }
+/// Return the WSL path of the file `name` next to `wslgit.exe`, or of
+/// `wslgit.exe` itself if `name` is `None`, if it is accessible from WSL.
+fn wsl_path_next_to_exe(name: Option<&str>, translator: &PathTranslator) -> Option<String> {
+    let exe = env::current_exe().ok()?;
+    let path = match name {
+        Some(name) => exe.with_file_name(name),
+        None => exe,
+    };
+    translator.to_unix(&path.to_string_lossy())
 }
 
-fn format_argument(arg: String) -> String {
+/// * `pathspec` - Whether the argument is after `--`.
+fn format_argument(
+    arg: String,
+    translator: &PathTranslator,
+    dist_bridge: DistBridge,
+    pathspec: bool,
+) -> Result<String, String> {
     if arg == "--" {
-        unsafe {
-            DOUBLE_DASH_FOUND = true;
-        };
-        return arg;
+        return Ok(arg);
     } else {
         let mut arg = arg;
         if fork::needs_patching() {
             arg = fork::patch_argument(arg);
         }
-        arg = translate_path_to_unix(arg);
-        arg = escape_characters(arg);
-        arg = quote_argument(arg);
-        arg
+        translate_path_to_unix(arg, translator, dist_bridge, pathspec)
     }
 }
 
 /// Return `true` if the git command can access remotes and therefore might need
 /// the setup of an interactive shell.
-fn git_command_needs_interactive_shell() -> bool {
+fn git_command_needs_interactive_shell(invocation: &Invocation) -> bool {
     const CMDS: &[&str] = &["clone", "fetch", "pull", "push", "ls-remote"];
-    env::args()
-        .skip(1)
-        .position(|arg| CMDS.iter().position(|&tcmd| tcmd == arg).is_some())
-        .is_some()
+    CMDS.iter().any(|cmd| invocation.is(cmd))
 }
 
-fn use_interactive_shell() -> bool {
-    // check for explicit environment variable setting
-    if let Ok(interactive_flag) = env::var("WSLGIT_USE_INTERACTIVE_SHELL") {
-        if interactive_flag == "false" || interactive_flag == "0" {
-            return false;
-        } else if interactive_flag == "smart" {
-            return git_command_needs_interactive_shell();
-        } else {
-            return true;
-        }
-    }
-    // check for advanced usage indicated by BASH_ENV and WSLENV contains BASH_ENV
-    else if env::var("BASH_ENV").is_ok() {
-        if let Ok(wslenv) = env::var("WSLENV") {
-            lazy_static! {
-                // BASH_ENV can be first or after another variable.
-                // It can be followed by flags, another variable or be last.
-                static ref BASH_ENV_RE: Regex = Regex::new(r"(?-u)(^|:)BASH_ENV(/|:|$)")
-                    .expect("Failed to compile BASH_ENV regex");
-            }
-            if BASH_ENV_RE.is_match(wslenv.as_bytes()) {
-                return false;
-            }
-        }
+/// * `bash_env_shared` - Whether a bash startup script is shared, see
+///   [`bash_env_is_shared`].
+fn use_interactive_shell(invocation: &Invocation, config: &Config, bash_env_shared: bool) -> bool {
+    match config.use_interactive_shell.value {
+        // check for advanced usage indicated by BASH_ENV and WSLENV contains BASH_ENV,
+        // unless explicitly configured
+        _ if config.use_interactive_shell.source == Source::Default && bash_env_shared => false,
+        InteractiveShell::Never => false,
+        InteractiveShell::Always => true,
+        InteractiveShell::Smart => git_command_needs_interactive_shell(invocation),
     }
-    // default
-    git_command_needs_interactive_shell()
+}
+
+/// Return `true` if a bash startup script is shared to WSL using `BASH_ENV`,
+/// i.e. `BASH_ENV` is set and `WSLENV` contains `BASH_ENV`.
+///
+/// The script is only executed if git is started through bash. The variables
+/// are looked up with `var`.
+fn bash_env_is_shared(var: impl Fn(&str) -> Option<String>) -> bool {
+    var("BASH_ENV").is_some()
+        && var("WSLENV").is_some_and(|wslenv| wsl::WslEnv::parse(&wslenv).contains("BASH_ENV"))
+}
+
+/// Return the git subcommands whose output is translated: `rev-parse`, `remote`
+/// and `init`, plus any configured in `translate_output_cmds`.
+fn translated_commands(config: &Config) -> Vec<String> {
+    let mut cmds: Vec<String> = vec!["rev-parse".into(), "remote".into(), "init".into()];
+    cmds.extend(config.translate_output_cmds.value.iter().cloned());
+    cmds
 }
 
 /// Find the working directory by starting from the current directory and applying
-/// any paths from `-C` or `--work-tree` arguments.
+/// any paths from `-C` or `--work-tree` options.
 ///
 /// `--git-dir` is ignored, it is assumed that both the work-tree and git-dir
 /// are on the same file system/same wsl distribution.
 ///
 /// Returns the working directory as a String.
-fn get_working_directory(current_dir: PathBuf, args: &[String]) -> String {
+fn get_working_directory(current_dir: PathBuf, invocation: &Invocation) -> String {
     let mut working_dir = current_dir;
-    let mut work_tree = env::var("GIT_WORK_TREE").unwrap_or("".to_string());
-
-    let mut skip_next = false;
-    let mut next_is_path = false;
-
-    for arg in args {
-        if skip_next {
-            skip_next = false;
-        } else if next_is_path {
-            next_is_path = false;
-            // let path = PathBuf::from(arg);
-            working_dir.push(arg);
-        } else if arg == "-c" {
-            // `-c` expects a second argument, so skip next argument.
-            skip_next = true;
-        } else if arg == "-C" {
-            // `-C` expects a second argument that is a path
-            next_is_path = true;
-        } else if arg.starts_with("--work-tree=") {
-            work_tree = arg[12..].to_string();
-        } else if !arg.starts_with("-") {
-            // First argument that doesn't start with '-' (or '--') is the git
-            // command; clone, commit etc.
-            break;
-        }
+
+    for dir in &invocation.directories {
+        working_dir.push(dir);
     }
 
-    // Finally apply the path from any "--work-tree" argument on the current working dir
-    if work_tree.len() > 0 {
+    // Finally apply the path from any "--work-tree" option on the current working dir
+    let work_tree = invocation
+        .work_tree
+        .clone()
+        .unwrap_or_else(|| env::var("GIT_WORK_TREE").unwrap_or_default());
+    if !work_tree.is_empty() {
         working_dir.push(work_tree);
     }
 
-    return working_dir.to_str().unwrap().to_string();
+    working_dir.to_str().unwrap().to_string()
 }
 
+/// Value of `default_dist` that selects a WSL1 distribution automatically.
+const AUTO_DIST: &str = "auto";
+
 /// Try to find the WSL distribution name from the provided `path`.
 ///
 /// An UNC prefix consists of \\server\share\, which is then followed by a path.
 /// When accessing a WSL filesystem using the `\\wsl$\dist\` UNC prefix then the
 /// distribution name can be extracted from the second component of the UNC
-/// prefix.
+/// prefix, see [`unc::parse`] for the supported forms.
 ///
-/// Returns the distribution name or None.
-fn get_wsl_dist_name(path: &str) -> Option<String> {
-    const UNC_SERVER_WSL: &str = "\\\\wsl$\\";
-    const UNC_SERVER_WSL_LOCALHOST: &str = "\\\\wsl.localhost\\";
-
-    let unc_path_without_server: Option<&str> = if path.starts_with(UNC_SERVER_WSL) {
-        Some(&path[UNC_SERVER_WSL.len()..])
-    } else if path.starts_with(UNC_SERVER_WSL_LOCALHOST) {
-        Some(&path[UNC_SERVER_WSL_LOCALHOST.len()..])
-    } else {
-        None
-    };
-
-    let wsl_dist_name = match unc_path_without_server {
-        Some(p) => {
-            // the string p starts with the UNC 'share', which is the wsl dist name
-            let (dist_name, _) = p.split_once('\\').unwrap();
-            Some(dist_name.to_string())
-        }
-        None => {
-            if let Ok(default_dist) = env::var("WSLGIT_DEFAULT_DIST") {
-                Some(default_dist)
-            } else {
-                // Use wsl default dist
-                None
-            }
+/// Otherwise the distribution of the first matching rule in `dist_rules` is
+/// used, then `default_dist`. With `default_dist = "auto"` the first installed
+/// WSL1 distribution that has git is used for paths outside of WSL, since WSL1
+/// is faster on the Windows filesystem and can access mapped network drives.
+///
+/// Returns the distribution name, or None for the WSL default distribution,
+/// and why it was chosen. Fails if `path` is a UNC path into WSL without a
+/// distribution name.
+fn select_wsl_dist(path: &str, config: &Config) -> Result<(Option<String>, String), UncError> {
+    match unc::parse(path) {
+        Ok(unc_path) => {
+            return Ok((
+                Some(unc_path.dist.to_string()),
+                "owns the working directory".to_string(),
+            ))
         }
-    };
+        Err(UncError::NotWslPath) => {}
+        Err(e) => return Err(e),
+    }
 
-    return wsl_dist_name;
+    // Use the dist of the first matching rule, then the configured dist,
+    // or wsl default dist if None
+    let paths = dist_rules::path_forms(path);
+    if let Some(rule) = dist_rules::find_rule(&config.dist_rules.value, &paths) {
+        return Ok((
+            Some(rule.dist.clone()),
+            format!(
+                "dist_rules entry {:?} from {}",
+                rule.pattern, config.dist_rules.source
+            ),
+        ));
+    }
+    Ok(match config.default_dist.value {
+        Some(ref dist) if dist.eq_ignore_ascii_case(AUTO_DIST) => match wsl::auto_distribution() {
+            Some(dist) => (
+                Some(dist),
+                format!(
+                    "first WSL1 distribution with git, default_dist from {}",
+                    config.default_dist.source
+                ),
+            ),
+            None => (
+                None,
+                format!(
+                    "no WSL1 distribution with git is installed, default_dist from {}",
+                    config.default_dist.source
+                ),
+            ),
+        },
+        Some(ref dist) => (
+            Some(dist.clone()),
+            format!("default_dist from {}", config.default_dist.source),
+        ),
+        None => (None, "the WSL default distribution".to_string()),
+    })
 }
 
-fn enable_logging() -> bool {
-    if let Ok(enable_log_flag) = env::var("WSLGIT_ENABLE_LOGGING") {
-        if enable_log_flag == "true" || enable_log_flag == "1" {
-            return true;
+/// The configuration of wslgit, loaded on first use.
+///
+/// Exits with an error message if a configuration file is invalid.
+fn config() -> &'static Config {
+    lazy_static! {
+        static ref CONFIG: Result<Config, ConfigError> = Config::load();
+    }
+    match *CONFIG {
+        Ok(ref config) => config,
+        Err(ref e) => {
+            eprintln!("wslgit: invalid configuration: {}", e);
+            std::process::exit(1);
         }
     }
-    false
+}
+
+fn enable_logging() -> bool {
+    config().enable_logging.value
 }
 
 fn log_arguments(out_args: &Vec<String>) {
@@ -358,34 +396,428 @@ fn log(message: String) {
     write!(&f, "{}\n", message).unwrap();
 }
 
+/// Error message for a distribution `name` that is not installed.
+fn unknown_dist_error(name: &str, distributions: &[wsl::Distribution]) -> String {
+    let names: Vec<&str> = distributions.iter().map(|d| d.name.as_str()).collect();
+    if names.is_empty() {
+        format!(
+            "the WSL distribution \"{}\" is not installed, there are no installed distributions",
+            name
+        )
+    } else {
+        format!(
+            "the WSL distribution \"{}\" is not installed, installed distributions are: {}",
+            name,
+            names.join(", ")
+        )
+    }
+}
+
+/// Check that the WSL distribution `name` is installed and return its name as
+/// listed by `wsl --list`.
+///
+/// Exits with an error listing the installed distributions if it is not. If
+/// the installed distributions cannot be determined, `name` is used as is.
+fn validate_dist_name(name: String) -> String {
+    let mut distributions = match wsl::list_distributions(false) {
+        Some(distributions) => distributions,
+        None => return name,
+    };
+    if wsl::find_distribution(&distributions, &name).is_none() {
+        // The cached list might be outdated
+        distributions = wsl::list_distributions(true).unwrap_or_default();
+    }
+    match wsl::find_distribution(&distributions, &name) {
+        Some(dist) => dist.name.clone(),
+        None => {
+            eprintln!("wslgit: {}", unknown_dist_error(&name, &distributions));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run the checks of `--doctor`, print a report (as JSON with `--json`) and
+/// exit with an error code if any of them failed.
+fn run_doctor(args: &[String], wsl_dist: Option<&str>, dist_reason: String) -> ! {
+    let env = doctor::Environment {
+        dist: wsl_dist,
+        dist_reason,
+        installed: wsl::list_distributions(true),
+        bash: BASH_EXECUTABLE,
+        interactive_shell: use_interactive_shell(
+            &Invocation::parse(&["fetch".into()]),
+            config(),
+            bash_env_is_shared(|key| env::var(key).ok()),
+        ),
+    };
+    let checks = doctor::run(&env);
+    if args.iter().any(|arg| arg == "--json") {
+        println!("{}", doctor::report_json(&checks));
+    } else {
+        print!("{}", doctor::report(&checks));
+    }
+    std::process::exit(if doctor::passed(&checks) { 0 } else { 1 });
+}
+
+/// Relay a request of git inside WSL to the configured Windows credential
+/// helper and exit.
+fn run_credential_helper(action: Option<&String>) -> ! {
+    let helper = match config().credential_helper.value {
+        Some(ref helper) => helper,
+        None => {
+            eprintln!("wslgit: credential_helper is not set");
+            std::process::exit(1);
+        }
+    };
+    let action = action.map_or("", String::as_str);
+    match credential::relay(helper, action, &mut io::stdin(), &mut io::stdout()) {
+        Ok((request, response)) => {
+            if enable_logging() {
+                log(format!(
+                    "credential {} {:?} -> {:?}",
+                    action,
+                    credential::redact(&request),
+                    credential::redact(&response)
+                ));
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("wslgit: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Relay a connection of an ssh client inside WSL to the configured Windows
+/// ssh-agent and exit.
+fn run_ssh_agent_relay() -> ! {
+    let pipe = match config().ssh_agent_pipe.value {
+        Some(ref pipe) => pipe,
+        None => {
+            eprintln!("wslgit: ssh_agent_pipe is not set");
+            std::process::exit(1);
+        }
+    };
+    match ssh_agent::run(pipe) {
+        Ok(requests) => {
+            if enable_logging() {
+                log(format!("relayed {} requests to the ssh-agent", requests));
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("wslgit: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Ask the askpass program of the caller for `prompt`, print the answer and
+/// exit.
+fn run_askpass(prompt: &[String]) -> ! {
+    match askpass::run(prompt) {
+        Ok(answer) => {
+            if enable_logging() {
+                log(format!("askpass {:?}", prompt));
+            }
+            println!("{}", answer);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("wslgit: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write `contents` to the stdin of `process` in the background and close it.
+fn write_stdin(process: &mut Child, contents: Option<Vec<u8>>) {
+    if let (Some(contents), Some(mut stdin)) = (contents, process.stdin.take()) {
+        thread::spawn(move || {
+            // git might exit before reading everything
+            let _ = stdin.write_all(&contents);
+        });
+    }
+}
+
 fn main() {
     let mut cmd_args = Vec::new();
-    let mut git_args: Vec<String> = vec![String::from("git")];
-    git_args.extend(env::args().skip(1).map(format_argument));
-
-    let git_cmd: String = git_args.join(" ");
 
     let curr_dir = env::current_dir().unwrap();
     // Assumes that the first element in args is the executable
     let args: Vec<String> = env::args().skip(1).collect();
-    let working_directory = get_working_directory(curr_dir, &args);
-    match get_wsl_dist_name(&working_directory) {
-        Some(wsl_dist) => {
-            cmd_args.push("--distribution".to_string());
-            cmd_args.push(wsl_dist.to_string());
+    let invoked_as_helper = env::current_exe().is_ok_and(|exe| {
+        exe.file_stem()
+            .is_some_and(|name| name.eq_ignore_ascii_case(credential::HELPER_NAME))
+    });
+    if invoked_as_helper {
+        run_credential_helper(args.first());
+    } else if args.first().map(String::as_str) == Some(credential::HELPER_OPTION) {
+        run_credential_helper(args.get(1));
+    }
+    if args.first().map(String::as_str) == Some(askpass::ASKPASS_OPTION) {
+        run_askpass(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some(ssh_agent::RELAY_OPTION) {
+        run_ssh_agent_relay();
+    }
+    if args.first().map(String::as_str) == Some("--wslgit-show-config") {
+        print!("{}", config());
+        return;
+    }
+    let invocation = Invocation::parse(&args);
+    let working_directory = get_working_directory(curr_dir, &invocation);
+    let (wsl_dist, dist_reason) = match select_wsl_dist(&working_directory, config()) {
+        Ok(selected) => selected,
+        Err(e) => {
+            eprintln!(
+                "wslgit: invalid working directory {:?}: {}",
+                working_directory, e
+            );
+            std::process::exit(1);
         }
-        None => {}
+    };
+    if args.first().map(String::as_str) == Some("--doctor") {
+        run_doctor(&args, wsl_dist.as_deref(), dist_reason);
+    }
+    if enable_logging() {
+        log(format!(
+            "using {} ({})",
+            wsl_dist
+                .as_deref()
+                .unwrap_or("the WSL default distribution"),
+            dist_reason
+        ));
+    }
+    let wsl_dist = wsl_dist.map(validate_dist_name);
+    if let Some(ref wsl_dist) = wsl_dist {
+        cmd_args.push("--distribution".to_string());
+        cmd_args.push(wsl_dist.to_string());
     }
 
+    // Paths into the WSL default distribution are translated natively too
+    let translator = PathTranslator::new(
+        MountTable::load(wsl_dist.as_deref()),
+        wsl_dist
+            .clone()
+            .or_else(wsl::default_distribution)
+            .map(|dist| translate::dist_share(&working_directory, &dist)),
+    );
+
+    // a path that cannot be translated, e.g. into another distribution that
+    // is not bridged, is an error. `first` is the index of `args[0]` in the
+    // arguments of wslgit, the pathspec after `--` starts at `pathspec_start`.
+    let dist_bridge = config().dist_bridge.value;
+    let pathspec_start = args.len() - invocation.pathspec.len();
+    let format_arguments = |first: usize, args: &[String]| -> Vec<String> {
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let pathspec = first + i >= pathspec_start;
+                format_argument(arg.clone(), &translator, dist_bridge, pathspec)
+            })
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| {
+                eprintln!("wslgit: {}", e);
+                std::process::exit(1);
+            })
+    };
+
+    let global_options_len = invocation.global_options.len();
+    let mut git_args: Vec<String> = vec![String::from("git")];
+    git_args.extend(format_arguments(0, &args[..global_options_len]));
+
+    // decide on the shell and output translation for the command an alias
+    // expands to
+    let invocation = match alias::resolve(
+        &invocation,
+        wsl_dist.as_deref(),
+        &git_args[1..],
+        &working_directory,
+    ) {
+        Some(expanded) => {
+            if enable_logging() {
+                log(format!(
+                    "alias {:?} expands to {:?} {:?}",
+                    invocation.subcommand.unwrap_or_default(),
+                    expanded.subcommand.as_deref().unwrap_or_default(),
+                    expanded.args
+                ));
+            }
+            expanded
+        }
+        None => invocation,
+    };
+
+    // files that git cannot access are copied into WSL or passed on stdin,
+    // before the remaining arguments are translated
+    let mut args = args;
+    let mut stdin_contents = None;
+    if let Some(ref subcommand) = invocation.subcommand {
+        if let Some(subcommand_args) = args.get_mut(global_options_len + 1..) {
+            let end = subcommand_args
+                .iter()
+                .position(|arg| arg == "--")
+                .unwrap_or(subcommand_args.len());
+            let original: Vec<String> = subcommand_args[..end].to_vec();
+            stdin_contents = match file_options::bridge_files(
+                subcommand,
+                &mut subcommand_args[..end],
+                &translator,
+                config().dist_bridge.value,
+            ) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    eprintln!("wslgit: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if enable_logging() && original[..] != subcommand_args[..end] {
+                log(format!(
+                    "bridged file options {:?} -> {:?}",
+                    original,
+                    &subcommand_args[..end]
+                ));
+            }
+        }
+    }
+    git_args.extend(format_arguments(
+        global_options_len,
+        &args[global_options_len..],
+    ));
+
+    // let git inside WSL use the Windows credential helper through wslgit
+    if let Some(ref helper) = config().credential_helper.value {
+        match wsl_path_next_to_exe(None, &translator) {
+            Some(wslgit_path) => {
+                // The helper runs as a child of git, which gets this from WSLENV
+                wsl::share_val("WSLGIT_CREDENTIAL_HELPER", helper, false);
+                git_args.splice(
+                    1..1,
+                    vec![
+                        "-c".to_string(),
+                        "credential.helper=".to_string(),
+                        "-c".to_string(),
+                        format!(
+                            "credential.helper={}",
+                            credential::helper_config(&wslgit_path)
+                        ),
+                    ],
+                );
+            }
+            None => {
+                if enable_logging() {
+                    log(
+                        "wslgit.exe is not accessible from WSL, the credential helper is not used"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    // forward the environment variables of the caller, e.g. GIT_AUTHOR_NAME,
+    // except those it already shares in WSLENV with flags of its own
+    let wslenv = wsl::WslEnv::from_env();
+    let forwarded = forward_env::select(
+        &config().forward_env.value,
+        env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .filter(|name| !wslenv.contains(name)),
+    );
+    for (name, flags) in &forwarded {
+        wsl::share_var(name, flags);
+    }
+    if enable_logging() && !forwarded.is_empty() {
+        log(format!("forwarded environment variables {:?}", forwarded));
+    }
+
+    // let git and ssh inside WSL ask the askpass program of the caller, e.g.
+    // VSCode, for passwords through wslgit
+    if askpass::original(|key| env::var(key).ok()).is_some() {
+        let paths = wsl_path_next_to_exe(Some(askpass::SHIM_NAME), &translator)
+            .zip(wsl_path_next_to_exe(None, &translator));
+        match paths {
+            Some((shim, wslgit_path)) => {
+                for (key, value) in
+                    askpass::shared_vars(|key| env::var(key).ok(), &shim, &wslgit_path)
+                {
+                    wsl::share_val(key, &value, false);
+                }
+            }
+            None => {
+                if enable_logging() {
+                    log(
+                        "wslgit.exe is not accessible from WSL, askpass is not forwarded"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    // run git through a script that relays SSH_AUTH_SOCK to the Windows
+    // ssh-agent, so that no interactive shell is needed to start an agent
+    if let Some(ref pipe) = config().ssh_agent_pipe.value {
+        let paths = wsl_path_next_to_exe(Some(ssh_agent::SHIM_NAME), &translator)
+            .zip(wsl_path_next_to_exe(None, &translator));
+        match paths {
+            Some((shim, wslgit_path)) => {
+                // The relay is started by the script, which gets these from WSLENV
+                wsl::share_val("WSLGIT_SSH_AGENT_EXE", &wslgit_path, false);
+                wsl::share_val("WSLGIT_SSH_AGENT_PIPE", pipe, false);
+                git_args.insert(0, shim);
+            }
+            None => {
+                if enable_logging() {
+                    log(
+                        "wslgit.exe is not accessible from WSL, the ssh-agent is not forwarded"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    let git_cmd: String = git_args.join(" ");
+
     // build the command arguments that are passed to wsl.exe
+    let mut output_marker = None;
+    let bash_env_shared = bash_env_is_shared(|key| env::var(key).ok());
     cmd_args.push("-e".to_string());
-    cmd_args.push(BASH_EXECUTABLE.to_string());
-    if use_interactive_shell() {
+    if use_interactive_shell(&invocation, config(), bash_env_shared) {
+        // Let bash set up the environment, e.g. start ssh-agent from .bashrc
+        cmd_args.push(BASH_EXECUTABLE.to_string());
         cmd_args.push("-ic".to_string());
-    } else {
+        if io::stdout().is_terminal() {
+            cmd_args.push(shell::command_line(&git_args));
+        } else {
+            // Mark the start of the git output, so that any output of .bashrc
+            // can be discarded before it reaches the caller
+            let marker = format!(
+                "__wslgit_output_start_{}_{}__",
+                std::process::id(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.subsec_nanos())
+            );
+            cmd_args.push(format!(
+                "printf '%s' {}; exec {}",
+                shell::quote(&marker),
+                shell::command_line(&git_args)
+            ));
+            output_marker = Some(marker);
+        }
+    } else if bash_env_shared {
+        // Let bash run the startup script from BASH_ENV
+        cmd_args.push(BASH_EXECUTABLE.to_string());
         cmd_args.push("-c".to_string());
+        cmd_args.push(shell::command_line(&git_args));
+    } else {
+        // Execute git directly, every argument is passed on unchanged
+        cmd_args.extend(git_args);
     }
-    cmd_args.push(git_cmd.clone());
 
     if enable_logging() {
         log(format!(
@@ -402,44 +834,66 @@ fn main() {
     let mut git_proc_setup = Command::new("wsl");
     git_proc_setup.args(&cmd_args);
 
-    let status;
-
-    // add git commands that must use translate_path_to_win
-    const TRANSLATED_CMDS: &[&str] = &["rev-parse", "remote", "init"];
-
-    let translate_output = env::args()
-        .skip(1)
-        .position(|arg| {
-            TRANSLATED_CMDS
+    // pass the output of some git commands through an output translator
+    let line_translator = invocation
+        .subcommand
+        .as_deref()
+        .filter(|&subcommand| {
+            translated_commands(config())
                 .iter()
-                .position(|&tcmd| tcmd == arg)
-                .is_some()
+                .any(|cmd| cmd == subcommand)
         })
-        .is_some();
+        .map(output::line_translator);
 
-    if translate_output {
-        // run the subprocess and capture its output
-        let git_proc = git_proc_setup
+    if stdin_contents.is_some() {
+        git_proc_setup.stdin(Stdio::piped());
+    }
+
+    let status = if line_translator.is_some() || output_marker.is_some() {
+        // run the subprocess and process its output while it is produced
+        let mut git_proc = git_proc_setup
             .stdout(Stdio::piped())
             .spawn()
             .expect(&format!("Failed to execute command '{}'", &git_cmd));
-        let output = git_proc
-            .wait_with_output()
-            .expect(&format!("Failed to wait for git call '{}'", &git_cmd));
-        status = output.status;
-        let output_bytes = output.stdout;
-        let mut stdout = io::stdout();
-        stdout
-            .write_all(&translate_path_to_win(&output_bytes))
-            .expect("Failed to write git output");
-        stdout.flush().expect("Failed to flush output");
+        write_stdin(&mut git_proc, stdin_contents);
+        let mut git_stdout: Box<dyn Read> = Box::new(git_proc.stdout.take().unwrap());
+        if let Some(ref marker) = output_marker {
+            let (discarded, rest) = output::skip_until(git_stdout, marker.as_bytes())
+                .expect("Failed to read git output");
+            if !discarded.is_empty() && enable_logging() {
+                log(format!(
+                    "discarded shell output {:?}",
+                    String::from_utf8_lossy(&discarded)
+                ));
+            }
+            git_stdout = Box::new(rest);
+        }
+        let stdout = io::stdout();
+        match line_translator {
+            Some(line_translator) => {
+                let separator = output::record_separator(&invocation.args);
+                let translate_path = |path: &str| translate_path_to_win(path, &translator);
+                output::translate_lines(git_stdout, &mut stdout.lock(), separator, |line| {
+                    output::translate_line(line, separator, line_translator, &translate_path)
+                })
+            }
+            None => io::copy(&mut git_stdout, &mut stdout.lock()).map(|_| ()),
+        }
+        .expect("Failed to write git output");
+        git_proc
+            .wait()
+            .expect(&format!("Failed to wait for git call '{}'", &git_cmd))
     } else {
         // run the subprocess without capturing its output
         // the output of the subprocess is passed through unchanged
-        status = git_proc_setup
-            .status()
+        let mut git_proc = git_proc_setup
+            .spawn()
             .expect(&format!("Failed to execute command '{}'", &git_cmd));
-    }
+        write_stdin(&mut git_proc, stdin_contents);
+        git_proc
+            .wait()
+            .unwrap_or_else(|_| panic!("Failed to wait for git call '{}'", &git_cmd))
+    };
 
     // forward any exit code
     if let Some(exit_code) = status.code() {
@@ -451,192 +905,294 @@ fn main() {
 mod tests {
     use super::*;
 
-    #[test]
-    fn use_interactive_shell_test() {
-        // default
-        env::remove_var("WSLGIT_USE_INTERACTIVE_SHELL");
-        env::remove_var("BASH_ENV");
-        env::remove_var("WSLENV");
-
-        // It is not possible to change env::args, so the arguments that are matched
-        // in git_command_needs_interactive_shell() are the arguments to cargo,
-        // which does not match any of the git commands that needs interactive shell.
-        let default_value = false;
-
-        assert_eq!(use_interactive_shell(), default_value);
-
-        // disable using WSLGIT_USE_INTERACTIVE_SHELL set to 'false' or '0'
-        env::set_var("WSLGIT_USE_INTERACTIVE_SHELL", "false");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLGIT_USE_INTERACTIVE_SHELL", "0");
-        assert_eq!(use_interactive_shell(), false);
-
-        // enable using WSLGIT_USE_INTERACTIVE_SHELL set to anything but 'false' and '0'
-        env::set_var("WSLGIT_USE_INTERACTIVE_SHELL", "true");
-        assert_eq!(use_interactive_shell(), true);
-        env::set_var("WSLGIT_USE_INTERACTIVE_SHELL", "1");
-        assert_eq!(use_interactive_shell(), true);
-
-        env::remove_var("WSLGIT_USE_INTERACTIVE_SHELL");
-
-        // just having BASH_ENV is not enough
-        env::set_var("BASH_ENV", "something");
-        assert_eq!(use_interactive_shell(), default_value);
-
-        // BASH_ENV must also be in WSLENV
-        env::set_var("WSLENV", "BASH_ENV");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "BASH_ENV/up");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "BASH_ENV:TMP");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "BASH_ENV/up:TMP");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "TMP:BASH_ENV");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "TMP:BASH_ENV/up");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "TMP:BASH_ENV:TMP");
-        assert_eq!(use_interactive_shell(), false);
-        env::set_var("WSLENV", "TMP:BASH_ENV/up:TMP");
-        assert_eq!(use_interactive_shell(), false);
+    fn config_with(vars: &[(&str, &str)]) -> Config {
+        Config::with_env(vars).unwrap()
+    }
 
-        // WSLGIT_USE_INTERACTIVE_SHELL overrides BASH_ENV
-        env::set_var("WSLGIT_USE_INTERACTIVE_SHELL", "true");
-        assert_eq!(use_interactive_shell(), true);
-        env::remove_var("WSLGIT_USE_INTERACTIVE_SHELL");
+    fn get_wsl_dist_name(path: &str, config: &Config) -> Option<String> {
+        select_wsl_dist(path, config)
+            .ok()
+            .and_then(|(dist, _)| dist)
+    }
 
-        env::set_var("WSLENV", "NOT_BASH_ENV/up");
-        assert_eq!(use_interactive_shell(), default_value);
+    fn translator() -> PathTranslator {
+        PathTranslator::new(MountTable::default(), None)
     }
 
     #[test]
-    fn escape_characters() {
-        assert_eq!(
-            super::escape_characters("ab\ncdef".to_string()),
-            "ab$\'\n\'cdef"
-        );
+    fn translated_commands_test() {
         assert_eq!(
-            super::escape_characters("ab\ncd ef".to_string()),
-            "ab$\'\n\'cd ef"
+            translated_commands(&config_with(&[])),
+            vec!["rev-parse", "remote", "init"]
         );
+
         assert_eq!(
-            super::escape_characters("--pretty=format:%H±.%aN±.%aE±.%at±.%cN±.%cE±.%ct±.%P±.%B<!--RevisionMessageEnd-->".to_string()),
-            "--pretty=format:%H±.%aN±.%aE±.%at±.%cN±.%cE±.%ct±.%P±.%B\\<\\!--RevisionMessageEnd--\\>"
+            translated_commands(&config_with(&[(
+                "WSLGIT_TRANSLATE_OUTPUT_CMDS",
+                "worktree, submodule,"
+            )])),
+            vec!["rev-parse", "remote", "init", "worktree", "submodule"]
         );
-        // Long arguments with newlines...
+    }
+
+    #[test]
+    fn use_interactive_shell_test() {
+        let interactive = |value: &str| config_with(&[("WSLGIT_USE_INTERACTIVE_SHELL", value)]);
+
+        // `status` does not need an interactive shell, `push` does
+        let status = Invocation::parse(&["status".into()]);
+        let push = Invocation::parse(&["push".into()]);
+        let log = Invocation::parse(&["log".into(), "--grep".into(), "push".into()]);
+        let default_value = false;
+        assert_eq!(use_interactive_shell(&push, &config_with(&[]), false), true);
+        assert_eq!(use_interactive_shell(&log, &config_with(&[]), false), false);
+
         assert_eq!(
-            super::escape_characters("--ab\ncdef".to_string()),
-            "--ab$\'\n\'cdef"
+            use_interactive_shell(&status, &config_with(&[]), false),
+            default_value
         );
+
+        // disable using WSLGIT_USE_INTERACTIVE_SHELL set to 'false' or '0'
         assert_eq!(
-            super::escape_characters("--ab\ncd ef".to_string()),
-            "--ab$\'\n\'cd ef"
+            use_interactive_shell(&status, &interactive("false"), false),
+            false
         );
         assert_eq!(
-            super::escape_characters("ab\"cd ef\"".to_string()),
-            "ab\\\"cd ef\\\""
+            use_interactive_shell(&status, &interactive("0"), false),
+            false
         );
-    }
 
-    #[test]
-    fn quote_argument_with_invalid_character() {
-        assert_eq!(quote_argument("abc def".to_string()), "\"abc def\"");
-        assert_eq!(quote_argument("abc(def".to_string()), "\"abc(def\"");
-        assert_eq!(quote_argument("abc)def".to_string()), "\"abc)def\"");
-        assert_eq!(quote_argument("abc|def".to_string()), "\"abc|def\"");
+        // enable using WSLGIT_USE_INTERACTIVE_SHELL set to anything but 'false' and '0'
         assert_eq!(
-            quote_argument("\\\"abc def\\\"".to_string()),
-            "\"\\\"abc def\\\"\""
+            use_interactive_shell(&status, &interactive("true"), false),
+            true
         );
         assert_eq!(
-            quote_argument("user.(name|email)".to_string()),
-            "\"user.(name|email)\""
+            use_interactive_shell(&status, &interactive("1"), false),
+            true
         );
-    }
 
-    #[test]
-    fn quote_long_argument_with_invalid_character() {
-        assert_eq!(quote_argument("--abc def".to_string()), "\"--abc def\"");
-        assert_eq!(quote_argument("--abc=def".to_string()), "--abc=def");
-        assert_eq!(quote_argument("--abc=d ef".to_string()), "\"--abc=d ef\"");
-        assert_eq!(quote_argument("--abc=d(ef".to_string()), "\"--abc=d(ef\"");
-        assert_eq!(quote_argument("--abc=d)ef".to_string()), "\"--abc=d)ef\"");
-        assert_eq!(quote_argument("--abc=d|ef".to_string()), "\"--abc=d|ef\"");
+        // only some git commands use interactive mode with 'smart'
+        assert_eq!(
+            use_interactive_shell(&status, &interactive("smart"), false),
+            false
+        );
         assert_eq!(
-            quote_argument("--pretty=format:a(b|c)d".to_string()),
-            "\"--pretty=format:a(b|c)d\""
+            use_interactive_shell(&push, &interactive("smart"), false),
+            true
         );
+
+        // a shared BASH_ENV disables interactive mode by default
+        assert_eq!(use_interactive_shell(&push, &config_with(&[]), true), false);
+
+        // WSLGIT_USE_INTERACTIVE_SHELL overrides BASH_ENV
         assert_eq!(
-            quote_argument("--pretty=format:a (b | c) d".to_string()),
-            "\"--pretty=format:a (b | c) d\""
+            use_interactive_shell(&status, &interactive("true"), true),
+            true
         );
-        // Long arguments with invalid characters in argument name
-        assert_eq!(quote_argument("--abc(def".to_string()), "\"--abc(def\"");
-        assert_eq!(quote_argument("--abc)def".to_string()), "\"--abc)def\"");
-        assert_eq!(quote_argument("--abc|def".to_string()), "\"--abc|def\"");
     }
 
     #[test]
-    fn quote_empty_argument() {
-        assert_eq!(quote_argument("".to_string()), "\"\"");
+    fn bash_env_shared_test() {
+        let shared = |vars: &[(&str, &str)]| bash_env_is_shared(config::env_vars(vars));
+
+        // just having BASH_ENV is not enough
+        assert!(!shared(&[("BASH_ENV", "something")]));
+
+        // BASH_ENV must also be in WSLENV
+        for &wslenv in &[
+            "BASH_ENV",
+            "BASH_ENV/up",
+            "BASH_ENV:TMP",
+            "BASH_ENV/up:TMP",
+            "TMP:BASH_ENV",
+            "TMP:BASH_ENV/up",
+            "TMP:BASH_ENV:TMP",
+            "TMP:BASH_ENV/up:TMP",
+        ] {
+            assert!(
+                shared(&[("BASH_ENV", "something"), ("WSLENV", wslenv)]),
+                "{}",
+                wslenv
+            );
+        }
+
+        assert!(!shared(&[
+            ("BASH_ENV", "something"),
+            ("WSLENV", "NOT_BASH_ENV/up")
+        ]));
+        assert!(!shared(&[("WSLENV", "BASH_ENV")]));
     }
 
     #[test]
     fn win_to_unix_path_trans() {
         assert_eq!(
-            translate_path_to_unix("D:\\test\\file.txt".to_string()),
-            "$(wslpath 'D:\\test\\file.txt')"
+            translate_path_to_unix(
+                "D:\\test\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "/mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix("D:/test/file.txt".to_string()),
-            "$(wslpath 'D:/test/file.txt')"
+            translate_path_to_unix(
+                "D:/test/file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "/mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix(" D:\\test\\file.txt".to_string()),
-            " $(wslpath 'D:\\test\\file.txt')"
+            translate_path_to_unix(
+                " D:\\test\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            " /mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix(" D:/test/file.txt".to_string()),
-            " $(wslpath 'D:/test/file.txt')"
+            translate_path_to_unix(
+                " D:/test/file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            " /mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix(":main:D:\\test\\file.txt".to_string()),
-            ":main:$(wslpath 'D:\\test\\file.txt')"
+            translate_path_to_unix(
+                ":main:D:\\test\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            ":main:/mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix(":main:D:/test/file.txt".to_string()),
-            ":main:$(wslpath 'D:/test/file.txt')"
+            translate_path_to_unix(
+                ":main:D:/test/file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            ":main:/mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix("1,1:D:\\test\\file.txt".to_string()),
-            "1,1:$(wslpath 'D:\\test\\file.txt')"
+            translate_path_to_unix(
+                "1,1:D:\\test\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "1,1:/mnt/d/test/file.txt"
         );
         assert_eq!(
-            translate_path_to_unix("1,1:D:/test/file.txt".to_string()),
-            "1,1:$(wslpath 'D:/test/file.txt')"
+            translate_path_to_unix(
+                "1,1:D:/test/file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "1,1:/mnt/d/test/file.txt"
+        );
+        assert_eq!(
+            translate_path_to_unix(
+                "C:\\Users\\test user\\my file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "/mnt/c/Users/test user/my file.txt"
         );
         assert_eq!(
-            translate_path_to_unix("C:\\Users\\test user\\my file.txt".to_string()),
-            "$(wslpath 'C:\\Users\\test user\\my file.txt')"
+            translate_path_to_unix(
+                "C:/Users/test user/my file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "/mnt/c/Users/test user/my file.txt"
         );
+        // Paths that neither wslgit nor wslpath can translate are passed on unchanged
         assert_eq!(
-            translate_path_to_unix("C:/Users/test user/my file.txt".to_string()),
-            "$(wslpath 'C:/Users/test user/my file.txt')"
+            translate_path_to_unix(
+                "\\\\wslgit-no-server\\to\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "\\\\wslgit-no-server\\to\\file.txt"
         );
+        // $ git commit --file="//wsl$/wslgit-no-dist/home/"
         assert_eq!(
-            translate_path_to_unix("\\\\path\\to\\file.txt".to_string()),
-            "$(wslpath '\\\\path\\to\\file.txt')"
+            translate_path_to_unix(
+                "\\\\wsl$\\wslgit-no-dist\\home".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "\\\\wsl$\\wslgit-no-dist\\home"
         );
-        // $ git commit --file="//wsl$/Ubuntu-20.04/home/"
         assert_eq!(
-            translate_path_to_unix("\\\\wsl$\\Ubuntu-20.04\\home".to_string()),
-            "$(wslpath '\\\\wsl$\\Ubuntu-20.04\\home')"
+            translate_path_to_unix(
+                "//wsl$/wslgit-no-dist/home".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "//wsl$/wslgit-no-dist/home"
+        );
+
+        // UNC paths into the distribution that git runs in are translated natively
+        let translator = PathTranslator::new(
+            MountTable::default(),
+            Some("\\\\wsl$\\Ubuntu-20.04".to_string()),
         );
         assert_eq!(
-            translate_path_to_unix("//wsl$/Ubuntu-20.04/home".to_string()),
-            "$(wslpath '//wsl$/Ubuntu-20.04/home')"
+            translate_path_to_unix(
+                "\\\\wsl$\\Ubuntu-20.04\\home".to_string(),
+                &translator,
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "/home"
         );
+        assert_eq!(
+            translate_path_to_unix(
+                "--file=//wsl$/Ubuntu-20.04/home/msg.txt".to_string(),
+                &translator,
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "--file=/home/msg.txt"
+        );
+        // and paths into another distribution are an error unless bridged
+        assert!(translate_path_to_unix(
+            "--file=//wsl$/Debian/tmp/msg.txt".to_string(),
+            &translator,
+            DistBridge::Off,
+            false
+        )
+        .unwrap_err()
+        .contains("set dist_bridge"));
     }
 
     #[test]
@@ -647,8 +1203,7 @@ mod tests {
             .arg("-c")
             .arg("wslpath C:\\")
             .output();
-        let prefix_bytes = translate_path_to_win(b"/");
-        let prefix = std::str::from_utf8(&prefix_bytes).unwrap();
+        let prefix = translate_path_to_win("/", &translator());
         if check_wslpath.is_err()
             || !check_wslpath.expect("bash output").status.success()
             || prefix == ""
@@ -670,19 +1225,30 @@ mod tests {
             .output()
             .expect("creating tmp test file");
         assert_eq!(
-            std::str::from_utf8(&translate_path_to_win(b"/tmp/wslgit test file")).unwrap(),
+            translate_path_to_win("/tmp/wslgit test file", &translator()),
             format!("{}tmp\\wslgit test file", prefix)
         );
+        let remote = output::line_translator("remote");
+        let translate_path = |path: &str| translate_path_to_win(path, &translator());
         assert_eq!(
-            std::str::from_utf8(&translate_path_to_win(
-                b"origin  /tmp/wslgit test file (fetch)"
+            std::str::from_utf8(&output::translate_line(
+                b"origin\t/tmp/wslgit test file (fetch)",
+                b'\n',
+                remote,
+                &translate_path
             ))
             .unwrap(),
-            format!("origin  {}tmp\\wslgit test file (fetch)", prefix)
+            format!("origin\t{}tmp\\wslgit test file (fetch)", prefix)
         );
         assert_eq!(
-            std::str::from_utf8(&translate_path_to_win(b"mirror  /tmp/wslgit test file (fetch)\nmirror  /tmp/wslgit test file (push)\n")).unwrap(),
-            format!("mirror  {0}tmp\\wslgit test file (fetch)\nmirror  {0}tmp\\wslgit test file (push)\n", prefix)
+            std::str::from_utf8(&output::translate_line(
+                b"mirror\t/tmp/wslgit test file (push)\n",
+                b'\n',
+                remote,
+                &translate_path
+            ))
+            .unwrap(),
+            format!("mirror\t{}tmp\\wslgit test file (push)\n", prefix)
         );
         Command::new("wsl")
             .arg("-e")
@@ -695,78 +1261,125 @@ mod tests {
 
     #[test]
     fn relative_path_translation() {
-        unsafe {
-            DOUBLE_DASH_FOUND = false;
-        }
-
         assert_eq!(
-            translate_path_to_unix("src\\main.rs".to_string()),
+            translate_path_to_unix(
+                "src\\main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "src/main.rs"
         );
         assert_eq!(
-            translate_path_to_unix("src/main.rs".to_string()),
+            translate_path_to_unix(
+                "src/main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "src/main.rs"
         );
         assert_eq!(
-            translate_path_to_unix(".\\src\\main.rs".to_string()),
+            translate_path_to_unix(
+                ".\\src\\main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "./src/main.rs"
         );
         assert_eq!(
-            translate_path_to_unix("./src/main.rs".to_string()),
+            translate_path_to_unix(
+                "./src/main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "./src/main.rs"
         );
         assert_eq!(
-            translate_path_to_unix("..\\wslgit\\src\\main.rs".to_string()),
+            translate_path_to_unix(
+                "..\\wslgit\\src\\main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "../wslgit/src/main.rs"
         );
         assert_eq!(
-            translate_path_to_unix("../wslgit/src/main.rs".to_string()),
+            translate_path_to_unix(
+                "../wslgit/src/main.rs".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "../wslgit/src/main.rs"
         );
 
         assert_eq!(
-            translate_path_to_unix("prefix:..\\wslgit\\src\\main.rs:postfix".to_string()),
+            translate_path_to_unix(
+                "prefix:..\\wslgit\\src\\main.rs:postfix".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "prefix:../wslgit/src/main.rs:postfix"
         );
 
         assert_eq!(
-            translate_path_to_unix("^remote\\..*".to_string()),
+            translate_path_to_unix(
+                "^remote\\..*".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "^remote\\..*"
         );
 
         assert_eq!(
-            translate_path_to_unix("\"prefix:..\\wslgit\\src\\main.rs\"".to_string()),
+            translate_path_to_unix(
+                "\"prefix:..\\wslgit\\src\\main.rs\"".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "\"prefix:../wslgit/src/main.rs\""
         );
     }
 
     #[test]
     fn relative_path_after_double_dash() {
-        unsafe {
-            DOUBLE_DASH_FOUND = false;
-        }
-        assert_eq!(format_argument("--".to_string()), "--");
-        assert_eq!(unsafe { DOUBLE_DASH_FOUND }, true);
-
-        unsafe {
-            DOUBLE_DASH_FOUND = false;
-        }
-        assert_eq!(format_argument("-".to_string()), "-");
-        assert_eq!(unsafe { DOUBLE_DASH_FOUND }, false);
-
-        unsafe {
-            DOUBLE_DASH_FOUND = false;
-        }
         assert_eq!(
-            format_argument("path\\to\\nonexisting\\file.txt".to_string()),
+            format_argument("--".to_string(), &translator(), DistBridge::Off, false).unwrap(),
+            "--"
+        );
+        assert_eq!(
+            format_argument(
+                "path\\to\\nonexisting\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "path\\to\\nonexisting\\file.txt"
         );
-
-        unsafe {
-            DOUBLE_DASH_FOUND = true;
-        }
         assert_eq!(
-            format_argument("path\\to\\nonexisting\\file.txt".to_string()),
+            format_argument(
+                "path\\to\\nonexisting\\file.txt".to_string(),
+                &translator(),
+                DistBridge::Off,
+                true
+            )
+            .unwrap(),
             "path/to/nonexisting/file.txt"
         );
     }
@@ -775,66 +1388,156 @@ mod tests {
     fn git_url_translation() {
         // URLs with ssh, git, http[s] or ftp[s] prefix should not be translated
         assert_eq!(
-            translate_path_to_unix("ssh://user@host.xz:22/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "ssh://user@host.xz:22/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "ssh://user@host.xz:22/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("ssh://user@host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "ssh://user@host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "ssh://user@host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("ssh://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "ssh://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "ssh://host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("user@host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "user@host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "user@host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "host.xz/path/to/repo.git/"
         );
 
         assert_eq!(
-            translate_path_to_unix("git://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "git://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "git://host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("http://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "http://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "http://host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("https://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "https://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "https://host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("ftp://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "ftp://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "ftp://host.xz/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("ftps://host.xz/path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "ftps://host.xz/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "ftps://host.xz/path/to/repo.git/"
         );
 
         assert_eq!(
-            translate_path_to_unix("file:///path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "file:///path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "file:///path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("file://C:/path/to/repo.git/".to_string()),
-            "file://$(wslpath 'C:/path/to/repo.git/')"
+            translate_path_to_unix(
+                "file://C:/path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "file:///mnt/c/path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("file://C:\\path\\to\\repo.git\\".to_string()),
-            "file://$(wslpath 'C:\\path\\to\\repo.git\\')"
+            translate_path_to_unix(
+                "file://C:\\path\\to\\repo.git\\".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "file:///mnt/c/path/to/repo.git/"
         );
 
         assert_eq!(
-            translate_path_to_unix("file://path/to/repo.git/".to_string()),
+            translate_path_to_unix(
+                "file://path/to/repo.git/".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "file://path/to/repo.git/"
         );
         assert_eq!(
-            translate_path_to_unix("file://path\\to\\repo.git\\".to_string()),
+            translate_path_to_unix(
+                "file://path\\to\\repo.git\\".to_string(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
             "file://path/to/repo.git/"
         );
     }
@@ -842,28 +1545,56 @@ mod tests {
     #[test]
     fn arguments_path_translation() {
         assert_eq!(
-            translate_path_to_unix("--file=C:\\some\\path.txt".to_owned()),
-            "--file=$(wslpath 'C:\\some\\path.txt')"
+            translate_path_to_unix(
+                "--file=C:\\some\\path.txt".to_owned(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "--file=/mnt/c/some/path.txt"
         );
         assert_eq!(
-            translate_path_to_unix("--file=C:/some/path.txt".to_owned()),
-            "--file=$(wslpath 'C:/some/path.txt')"
+            translate_path_to_unix(
+                "--file=C:/some/path.txt".to_owned(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "--file=/mnt/c/some/path.txt"
         );
 
         assert_eq!(
-            translate_path_to_unix("-c core.editor=C:\\some\\editor.exe".to_owned()),
-            "-c core.editor=$(wslpath 'C:\\some\\editor.exe')"
+            translate_path_to_unix(
+                "-c core.editor=C:\\some\\editor.exe".to_owned(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "-c core.editor=/mnt/c/some/editor.exe"
         );
         assert_eq!(
-            translate_path_to_unix("-c core.editor=C:/some/editor.exe".to_owned()),
-            "-c core.editor=$(wslpath 'C:/some/editor.exe')"
+            translate_path_to_unix(
+                "-c core.editor=C:/some/editor.exe".to_owned(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "-c core.editor=/mnt/c/some/editor.exe"
         );
 
         assert_eq!(
             translate_path_to_unix(
-                "-c \"credential.helper=C:/Program Files/SmartGit/lib/credentials.cmd\"".to_owned()
-            ),
-            "-c \"credential.helper=$(wslpath 'C:/Program Files/SmartGit/lib/credentials.cmd')\""
+                "-c \"credential.helper=C:/Program Files/SmartGit/lib/credentials.cmd\"".to_owned(),
+                &translator(),
+                DistBridge::Off,
+                false
+            )
+            .unwrap(),
+            "-c \"credential.helper=/mnt/c/Program Files/SmartGit/lib/credentials.cmd\""
         );
     }
 
@@ -873,23 +1604,26 @@ mod tests {
 
         let args: Vec<String> = vec![];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\".to_string()
         );
         assert_eq!(
-            get_working_directory(PathBuf::from("\\\\wsl$\\dist-name\\repo\\"), &args),
+            get_working_directory(
+                PathBuf::from("\\\\wsl$\\dist-name\\repo\\"),
+                &Invocation::parse(&args)
+            ),
             "\\\\wsl$\\dist-name\\repo\\".to_string()
         );
 
         let args: Vec<String> = vec!["cmd".into()];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\".to_string()
         );
 
         let args: Vec<String> = vec!["-c".into(), "arg".into(), "cmd".into()];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\".to_string()
         );
 
@@ -901,7 +1635,7 @@ mod tests {
             "cmd".into(),
         ];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\relative".to_string()
         );
 
@@ -913,7 +1647,7 @@ mod tests {
             "cmd".into(),
         ];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\absolute".to_string()
         );
 
@@ -927,7 +1661,7 @@ mod tests {
             "cmd".into(),
         ];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\a\\b".to_string()
         );
 
@@ -940,7 +1674,7 @@ mod tests {
             "cmd".into(),
         ];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\a\\b".to_string()
         );
 
@@ -954,42 +1688,139 @@ mod tests {
             "cmd".into(),
         ];
         assert_eq!(
-            get_working_directory(PathBuf::from("C:\\repo\\"), &args),
+            get_working_directory(PathBuf::from("C:\\repo\\"), &Invocation::parse(&args)),
             "C:\\repo\\a\\c".to_string()
         );
     }
 
     #[test]
     fn wsl_dist_name() {
-        env::remove_var("WSLGIT_DEFAULT_DIST");
         assert_eq!(
-            get_wsl_dist_name(&r"\\wsl$\dist-name\a\b\c".to_string()),
+            get_wsl_dist_name(&r"\\wsl$\dist-name\a\b\c".to_string(), &config_with(&[])),
             Some("dist-name".to_string())
         );
         assert_eq!(
-            get_wsl_dist_name(&r"\\wsl.localhost\dist-name\a\b\c".to_string()),
+            get_wsl_dist_name(
+                &r"\\wsl.localhost\dist-name\a\b\c".to_string(),
+                &config_with(&[])
+            ),
             Some("dist-name".to_string())
         );
         assert_eq!(
-            get_wsl_dist_name(&r"\\server\dist-name\a\b\c".to_string()),
+            get_wsl_dist_name(&r"\\server\dist-name\a\b\c".to_string(), &config_with(&[])),
+            None
+        );
+        assert_eq!(
+            get_wsl_dist_name(&r"C:\a\b\c".to_string(), &config_with(&[])),
             None
         );
-        assert_eq!(get_wsl_dist_name(&r"C:\a\b\c".to_string()), None);
+        // The root of a distribution and other spellings of the UNC path
+        assert_eq!(
+            get_wsl_dist_name(r"\\wsl$\dist-name", &config_with(&[])),
+            Some("dist-name".to_string())
+        );
+        assert_eq!(
+            get_wsl_dist_name("//WSL$/dist-name/a", &config_with(&[])),
+            Some("dist-name".to_string())
+        );
+        assert_eq!(
+            get_wsl_dist_name(r"\\?\UNC\wsl.localhost\dist-name\a", &config_with(&[])),
+            Some("dist-name".to_string())
+        );
+        assert_eq!(
+            select_wsl_dist(r"\\wsl$\", &config_with(&[])),
+            Err(UncError::MissingDistribution)
+        );
+    }
+
+    #[test]
+    fn wsl_dist_name_precedence() {
+        let config = config_with(&[
+            ("WSLGIT_DEFAULT_DIST", "some-dist"),
+            (
+                "WSLGIT_DIST_RULES",
+                r"D:\work\new=new-dist;D:\work=work-dist;\\wsl$\**=rule-dist",
+            ),
+        ]);
+
+        // The distribution of a UNC path wins over all rules
+        assert_eq!(
+            get_wsl_dist_name(r"\\wsl$\dist-name\a", &config),
+            Some("dist-name".to_string())
+        );
+        // The first matching rule wins
+        assert_eq!(
+            get_wsl_dist_name(r"D:\work\new\repo", &config),
+            Some("new-dist".to_string())
+        );
+        assert_eq!(
+            get_wsl_dist_name(r"d:\WORK\old", &config),
+            Some("work-dist".to_string())
+        );
+        // Rules win over the default dist, which applies to these paths too
+        assert_eq!(
+            get_wsl_dist_name(r"D:\work\repo", &config),
+            Some("work-dist".to_string())
+        );
+        // Paths that no rule matches fall back to the default dist
+        assert_eq!(
+            get_wsl_dist_name(r"D:\other", &config),
+            Some("some-dist".to_string())
+        );
+    }
+
+    #[test]
+    fn wsl_dist_name_auto() {
+        let config = config_with(&[
+            ("WSLGIT_DEFAULT_DIST", "auto"),
+            ("WSLGIT_DIST_RULES", r"D:\work=work-dist"),
+        ]);
+
+        // The owning distribution of a UNC path is kept
+        assert_eq!(
+            select_wsl_dist(r"\\wsl$\dist-name\a", &config),
+            Ok((
+                Some("dist-name".to_string()),
+                "owns the working directory".to_string()
+            ))
+        );
+        // Rules win over the automatic choice
+        assert_eq!(
+            get_wsl_dist_name(r"D:\work\repo", &config),
+            Some("work-dist".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_dist_name() {
+        let distributions = wsl::parse_distributions(
+            "  NAME      STATE      VERSION\n* Ubuntu    Running    2\n  Debian    Stopped    1\n",
+        );
+        assert_eq!(
+            unknown_dist_error("Ubunut", &distributions),
+            "the WSL distribution \"Ubunut\" is not installed, \
+             installed distributions are: Ubuntu, Debian"
+        );
+        assert_eq!(
+            unknown_dist_error("Ubuntu", &[]),
+            "the WSL distribution \"Ubuntu\" is not installed, \
+             there are no installed distributions"
+        );
     }
 
     #[test]
     fn wsl_default_dist_name() {
-        env::set_var("WSLGIT_DEFAULT_DIST", "some-dist");
+        let config = config_with(&[("WSLGIT_DEFAULT_DIST", "some-dist")]);
         assert_eq!(
-            get_wsl_dist_name(&r"\\wsl$\dist-name\a\b\c".to_string()),
+            get_wsl_dist_name(&r"\\wsl$\dist-name\a\b\c".to_string(), &config),
             Some("dist-name".to_string())
         );
         assert_eq!(
-            get_wsl_dist_name(&r"\\server\dist-name\a\b\c".to_string()),
+            get_wsl_dist_name(&r"\\server\dist-name\a\b\c".to_string(), &config),
             Some("some-dist".to_string())
         );
         assert_eq!(
-            get_wsl_dist_name(&r"C:\a\b\c".to_string()),
+            get_wsl_dist_name(&r"C:\a\b\c".to_string(), &config),
             Some("some-dist".to_string())
         );
     }
@@ -0,0 +1,435 @@
+use std::process::Command;
+use std::time::Duration;
+
+use cache;
+
+/// Default mount point of the Windows drives inside WSL.
+pub const DEFAULT_AUTOMOUNT_ROOT: &str = "/mnt/";
+
+/// How long the mount table of a distribution is cached.
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A Windows drive or network share mounted inside WSL.
+#[derive(Debug, PartialEq)]
+pub struct Mount {
+    /// Windows side of the mount, e.g. `C:` or `\\server\share`.
+    pub source: String,
+    /// Mount point inside WSL, e.g. `/mnt/c`.
+    pub target: String,
+}
+
+/// Model of where Windows drives and network shares are mounted inside a WSL
+/// distribution.
+#[derive(Debug, PartialEq)]
+pub struct MountTable {
+    /// Where drives are automatically mounted, always ends with `/`.
+    automount_root: String,
+    mounts: Vec<Mount>,
+}
+
+impl Default for MountTable {
+    fn default() -> MountTable {
+        MountTable::new(DEFAULT_AUTOMOUNT_ROOT, Vec::new())
+    }
+}
+
+/// Read the `root` setting of the `[automount]` section from the contents of
+/// a `wsl.conf` file.
+pub fn parse_automount_root(wsl_conf: &str) -> Option<String> {
+    let mut in_automount_section = false;
+    for line in wsl_conf.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_automount_section = line.eq_ignore_ascii_case("[automount]");
+        } else if in_automount_section {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim().eq_ignore_ascii_case("root") {
+                    // Strip trailing comments and quotes
+                    let value = value.split('#').next().unwrap().trim();
+                    let value = value.trim_matches('"').trim_matches('\'');
+                    if !value.is_empty() {
+                        return Some(value.to_string());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Undo the octal escaping of spaces, tabs and backslashes in `/proc/mounts`.
+fn unescape_mount_field(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        result.push_str(&rest[..pos]);
+        let escaped = rest.get(pos + 1..pos + 4);
+        match escaped.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Normalize the Windows side of a mount to `C:` or `\\server\share`.
+fn normalize_source(source: &str) -> Option<String> {
+    let source = source.trim_end_matches('\\').trim_end_matches('/');
+    let bytes = source.as_bytes();
+    if bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        Some(source.to_ascii_uppercase())
+    } else if source.starts_with("\\\\") || source.starts_with("//") {
+        Some(source.replace('/', "\\"))
+    } else {
+        None
+    }
+}
+
+/// Parse a single line of `/proc/mounts` and return it if it mounts a Windows
+/// drive or network share.
+///
+/// WSL1 uses the `drvfs` filesystem type, WSL2 uses `9p` (or `virtiofs`) with
+/// `aname=drvfs` and the Windows path as `path=` in the mount options.
+fn parse_mount_line(line: &str) -> Option<Mount> {
+    let mut fields = line.split_whitespace();
+    let device = unescape_mount_field(fields.next()?);
+    let target = unescape_mount_field(fields.next()?);
+    let fs_type = fields.next()?;
+    let options = unescape_mount_field(fields.next().unwrap_or(""));
+
+    let is_drvfs = fs_type == "drvfs" || options.split(&[',', ';'][..]).any(|o| o == "aname=drvfs");
+    if !is_drvfs {
+        return None;
+    }
+
+    let path_option = options
+        .split(&[',', ';'][..])
+        .find_map(|o| o.strip_prefix("path="));
+    let source =
+        normalize_source(path_option.unwrap_or(&device)).or_else(|| normalize_source(&device))?;
+
+    Some(Mount {
+        source,
+        target: target.trim_end_matches('/').to_string(),
+    })
+}
+
+/// Return the part of `path` after `prefix` if `path` starts with `prefix`,
+/// ignoring ASCII case, and the prefix ends at a component boundary.
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str, separator: char) -> Option<&'a str> {
+    let head = path.get(..prefix.len())?;
+    let rest = &path[prefix.len()..];
+    let matches = if separator == '\\' {
+        head.eq_ignore_ascii_case(prefix)
+    } else {
+        head == prefix
+    };
+    if matches && (rest.is_empty() || rest.starts_with(separator)) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+impl MountTable {
+    /// * `automount_root` - Where drives are automatically mounted, e.g. `/mnt/`.
+    /// * `mounts` - Drives and network shares currently mounted.
+    pub fn new(automount_root: &str, mounts: Vec<Mount>) -> MountTable {
+        let mut automount_root = automount_root.to_string();
+        if !automount_root.ends_with('/') {
+            automount_root.push('/');
+        }
+        MountTable {
+            automount_root,
+            mounts,
+        }
+    }
+
+    /// Build the mount table from the contents of the distribution's
+    /// `/etc/wsl.conf` and `/proc/mounts`.
+    pub fn parse(wsl_conf: &str, proc_mounts: &str) -> MountTable {
+        let automount_root =
+            parse_automount_root(wsl_conf).unwrap_or_else(|| DEFAULT_AUTOMOUNT_ROOT.to_string());
+        let mounts = proc_mounts.lines().filter_map(parse_mount_line).collect();
+        MountTable::new(&automount_root, mounts)
+    }
+
+    /// Load the mount table of the WSL distribution `dist`, or the default
+    /// distribution if `None`.
+    ///
+    /// The files are read by starting WSL once, the result is cached for a
+    /// few minutes. If WSL cannot be started the default mount table is used.
+    pub fn load(dist: Option<&str>) -> MountTable {
+        let cache_key = format!("mounts-{}", dist.unwrap_or("default"));
+        // A cached value without the separator is not used, it might have
+        // been cut off
+        let cached = cache::read(&cache_key, CACHE_TTL).filter(|contents| contents.contains('\0'));
+        let contents = match cached {
+            Some(contents) => contents,
+            None => {
+                let mut cmd = Command::new("wsl");
+                if let Some(dist) = dist {
+                    cmd.arg("--distribution").arg(dist);
+                }
+                let output = cmd
+                    .arg("-e")
+                    .arg("/bin/sh")
+                    .arg("-c")
+                    .arg("cat /etc/wsl.conf 2>/dev/null; printf '\\000'; cat /proc/mounts")
+                    .output();
+                match output {
+                    Ok(ref output) if output.status.success() => {
+                        let contents = String::from_utf8_lossy(&output.stdout).into_owned();
+                        cache::write(&cache_key, &contents);
+                        contents
+                    }
+                    _ => return MountTable::default(),
+                }
+            }
+        };
+        let (wsl_conf, proc_mounts) = contents.split_once('\0').unwrap_or(("", &contents));
+        MountTable::parse(wsl_conf, proc_mounts)
+    }
+
+    /// Return `true` if the Windows path `path` is on a drive or network share
+    /// that is mounted inside WSL.
+    ///
+    /// Always `true` if no mounts are known, e.g. if the mount table could not
+    /// be loaded.
+    pub fn is_mounted(&self, path: &str) -> bool {
+        let path = path.replace('/', "\\");
+        self.mounts.is_empty()
+            || self
+                .mounts
+                .iter()
+                .any(|m| strip_path_prefix(&path, &m.source, '\\').is_some())
+    }
+
+    /// Translate a Windows drive path, or a path on a mounted network share,
+    /// to the corresponding path inside WSL.
+    pub fn to_unix(&self, path: &str) -> Option<String> {
+        let path = path.replace('/', "\\");
+
+        // Prefer the longest matching mount, e.g. a share mounted below a drive
+        let mount = self
+            .mounts
+            .iter()
+            .filter_map(|m| strip_path_prefix(&path, &m.source, '\\').map(|rest| (m, rest)))
+            .max_by_key(|&(m, _)| m.source.len());
+        if let Some((mount, rest)) = mount {
+            return Some(format!("{}{}", mount.target, rest.replace('\\', "/")));
+        }
+
+        // Drives that were not listed are expected below the automount root
+        let bytes = path.as_bytes();
+        if bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'\\'
+        {
+            let drive = (bytes[0] as char).to_ascii_lowercase();
+            return Some(format!(
+                "{}{}/{}",
+                self.automount_root,
+                drive,
+                path[3..].replace('\\', "/")
+            ));
+        }
+
+        None
+    }
+
+    /// Translate a path inside WSL to a Windows path if it is below the mount
+    /// point of a Windows drive or network share.
+    pub fn to_win(&self, path: &str) -> Option<String> {
+        let mount = self
+            .mounts
+            .iter()
+            .filter_map(|m| strip_path_prefix(path, &m.target, '/').map(|rest| (m, rest)))
+            .max_by_key(|&(m, _)| m.target.len());
+        if let Some((mount, rest)) = mount {
+            let rest = if rest.is_empty() {
+                "\\".to_string()
+            } else {
+                rest.replace('/', "\\")
+            };
+            return Some(format!("{}{}", mount.source, rest));
+        }
+
+        // Drives that were not listed are expected below the automount root
+        let rest = path.strip_prefix(self.automount_root.as_str())?;
+        let bytes = rest.as_bytes();
+        if !bytes.is_empty()
+            && bytes[0].is_ascii_alphabetic()
+            && (bytes.len() == 1 || bytes[1] == b'/')
+        {
+            let drive = (bytes[0] as char).to_ascii_uppercase();
+            let rest = if bytes.len() > 1 { &rest[2..] } else { "" };
+            return Some(format!("{}:\\{}", drive, rest.replace('/', "\\")));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WSL1_MOUNTS: &str = "\
+rootfs / lxfs rw,noatime 0 0
+none /dev tmpfs rw,noatime,mode=755 0 0
+C:\\134 /mnt/c drvfs rw,noatime,uid=1000,gid=1000,case=off 0 0
+D:\\134 /mnt/d drvfs rw,noatime,uid=1000,gid=1000,case=off 0 0
+\\134\\134server\\134my\\040share /mnt/share drvfs rw,relatime,uid=1000,gid=1000 0 0
+Z: /z drvfs rw,noatime,uid=1000,gid=1000 0 0
+";
+
+    const WSL2_MOUNTS: &str = "\
+/dev/sdc / ext4 rw,relatime,discard,errors=remount-ro,data=ordered 0 0
+none /mnt/wsl tmpfs rw,relatime 0 0
+drvfs /c 9p rw,noatime,dirsync,aname=drvfs;path=C:\\;uid=1000;gid=1000;symlinkroot=/,mmap,access=client,msize=262144,trans=virtio 0 0
+E:\\134 /e 9p rw,noatime,dirsync,aname=drvfs;path=E:\\134;uid=1000;gid=1000;symlinkroot=/ 0 0
+";
+
+    #[test]
+    fn automount_root_from_wsl_conf() {
+        let cases: &[(&str, Option<&str>)] = &[
+            ("", None),
+            ("[automount]\nroot = /\n", Some("/")),
+            (
+                "[automount]\nenabled=true\nroot=/windir/ # comment\n",
+                Some("/windir/"),
+            ),
+            ("[automount]\nroot = \"/drives\"\n", Some("/drives")),
+            (
+                "[network]\nroot = /\n[automount]\noptions = \"metadata\"\n",
+                None,
+            ),
+            ("[Automount]\nRoot = /\n", Some("/")),
+        ];
+        for &(wsl_conf, expected) in cases {
+            assert_eq!(
+                parse_automount_root(wsl_conf),
+                expected.map(|s| s.to_string()),
+                "{:?}",
+                wsl_conf
+            );
+        }
+    }
+
+    #[test]
+    fn parse_wsl1_mounts() {
+        let table = MountTable::parse("", WSL1_MOUNTS);
+        assert_eq!(table.automount_root, "/mnt/");
+        assert_eq!(
+            table.mounts,
+            vec![
+                Mount {
+                    source: "C:".to_string(),
+                    target: "/mnt/c".to_string()
+                },
+                Mount {
+                    source: "D:".to_string(),
+                    target: "/mnt/d".to_string()
+                },
+                Mount {
+                    source: "\\\\server\\my share".to_string(),
+                    target: "/mnt/share".to_string()
+                },
+                Mount {
+                    source: "Z:".to_string(),
+                    target: "/z".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_wsl2_mounts() {
+        let table = MountTable::parse("[automount]\nroot = /\n", WSL2_MOUNTS);
+        assert_eq!(table.automount_root, "/");
+        assert_eq!(
+            table.mounts,
+            vec![
+                Mount {
+                    source: "C:".to_string(),
+                    target: "/c".to_string()
+                },
+                Mount {
+                    source: "E:".to_string(),
+                    target: "/e".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_with_mounts() {
+        let table = MountTable::parse("", WSL1_MOUNTS);
+        let to_unix: &[(&str, Option<&str>)] = &[
+            ("C:\\Foo", Some("/mnt/c/Foo")),
+            ("c:/Foo/bar", Some("/mnt/c/Foo/bar")),
+            ("Z:\\repo", Some("/z/repo")),
+            ("\\\\server\\my share\\repo", Some("/mnt/share/repo")),
+            ("\\\\SERVER\\My Share", Some("/mnt/share")),
+            ("\\\\server\\my shared\\repo", None),
+            ("\\\\other\\share\\repo", None),
+            // Not mounted, but expected below the automount root
+            ("F:\\Foo", Some("/mnt/f/Foo")),
+        ];
+        for &(input, expected) in to_unix {
+            assert_eq!(
+                table.to_unix(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+
+        let to_win: &[(&str, Option<&str>)] = &[
+            ("/mnt/c/Foo", Some("C:\\Foo")),
+            ("/mnt/c", Some("C:\\")),
+            ("/z/repo", Some("Z:\\repo")),
+            ("/zz/repo", None),
+            ("/mnt/share/repo", Some("\\\\server\\my share\\repo")),
+            ("/mnt/f/Foo", Some("F:\\Foo")),
+            ("/home/me", None),
+        ];
+        for &(input, expected) in to_win {
+            assert_eq!(
+                table.to_win(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn mounted_paths() {
+        let table = MountTable::parse("", WSL1_MOUNTS);
+        assert!(table.is_mounted("C:\\Users\\me"));
+        assert!(table.is_mounted("d:/tmp"));
+        assert!(table.is_mounted("\\\\server\\my share\\repo"));
+        assert!(!table.is_mounted("F:\\Foo"));
+        assert!(!table.is_mounted("\\\\other\\share\\repo"));
+        assert!(MountTable::default().is_mounted("F:\\Foo"));
+    }
+
+    #[test]
+    fn translate_with_custom_root() {
+        let table = MountTable::parse("[automount]\nroot = /\n", WSL2_MOUNTS);
+        assert_eq!(table.to_unix("C:\\Foo"), Some("/c/Foo".to_string()));
+        assert_eq!(table.to_unix("G:\\Foo"), Some("/g/Foo".to_string()));
+        assert_eq!(table.to_win("/e/Foo"), Some("E:\\Foo".to_string()));
+        assert_eq!(table.to_win("/home/me"), None);
+    }
+}
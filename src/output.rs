@@ -0,0 +1,634 @@
+use regex::Regex;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+
+/// Maximum number of bytes that are buffered while waiting for the end of a
+/// line. Longer lines are passed on untranslated.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// Maximum number of bytes that are discarded while waiting for the start
+/// marker. If there is more output, it is passed on unchanged.
+const MAX_DISCARDED_OUTPUT: usize = 1024 * 1024;
+
+/// Options that make git terminate output records with NUL instead of newline.
+const NUL_OPTIONS: &[&str] = &["-z", "--null", "-0"];
+
+/// Return the byte that terminates the records in the output of git when it
+/// is called with `args`: NUL if `-z`, `--null` or `-0` is given, otherwise
+/// newline.
+pub fn record_separator(args: &[String]) -> u8 {
+    let null_output = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| NUL_OPTIONS.contains(&arg.as_str()));
+    if null_output {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+/// Discard everything from `reader` up to and including the first `marker`,
+/// e.g. output of the startup scripts of the shell that runs git.
+///
+/// Returns the discarded output and a reader for the remaining output. If the
+/// marker is not found, nothing is discarded.
+pub fn skip_until<R: Read>(mut reader: R, marker: &[u8]) -> io::Result<(Vec<u8>, impl Read)> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 4096];
+    // The search resumes where a marker spanning the previous and the next
+    // read could start, so that each byte is only searched once
+    let mut start = 0;
+    loop {
+        if let Some(pos) = buffer[start..]
+            .windows(marker.len())
+            .position(|window| window == marker)
+            .map(|pos| start + pos)
+        {
+            let rest = buffer.split_off(pos + marker.len());
+            buffer.truncate(pos);
+            return Ok((buffer, Cursor::new(rest).chain(reader)));
+        }
+        if buffer.len() > MAX_DISCARDED_OUTPUT {
+            break;
+        }
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        start = buffer.len().saturating_sub(marker.len().saturating_sub(1));
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    Ok((Vec::new(), Cursor::new(buffer).chain(reader)))
+}
+
+/// Copy the output of git from `reader` to `writer` while it is produced,
+/// passing every record terminated by `separator` (usually a line) through
+/// `translate`.
+///
+/// Each record is written and flushed as soon as it is complete, so only a
+/// single record is kept in memory at a time.
+pub fn translate_lines<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    separator: u8,
+    translate: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&[u8]) -> Vec<u8>,
+{
+    translate_lines_bounded(reader, writer, separator, MAX_LINE_LENGTH, translate)
+}
+
+fn translate_lines_bounded<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    separator: u8,
+    max_line_length: usize,
+    mut translate: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&[u8]) -> Vec<u8>,
+{
+    let mut reader = BufReader::with_capacity(max_line_length, reader);
+    // Start of the current line, if it did not fit into the reader's buffer
+    let mut partial_line: Vec<u8> = Vec::new();
+    // Set if the current line was too long and is passed on untranslated
+    let mut passthrough = false;
+
+    loop {
+        let (consumed, line_complete) = {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            match buf.iter().position(|&b| b == separator) {
+                Some(pos) => {
+                    let line = &buf[..=pos];
+                    if passthrough || partial_line.len() + line.len() > max_line_length {
+                        writer.write_all(&partial_line)?;
+                        writer.write_all(line)?;
+                    } else if partial_line.is_empty() {
+                        writer.write_all(&translate(line))?;
+                    } else {
+                        partial_line.extend_from_slice(line);
+                        writer.write_all(&translate(&partial_line))?;
+                    }
+                    (pos + 1, true)
+                }
+                None => {
+                    if passthrough {
+                        writer.write_all(buf)?;
+                    } else if partial_line.len() + buf.len() > max_line_length {
+                        // Too long to be a path, give up translating this line
+                        writer.write_all(&partial_line)?;
+                        writer.write_all(buf)?;
+                        partial_line.clear();
+                        passthrough = true;
+                    } else {
+                        partial_line.extend_from_slice(buf);
+                    }
+                    (buf.len(), false)
+                }
+            }
+        };
+        reader.consume(consumed);
+
+        if line_complete {
+            partial_line.clear();
+            passthrough = false;
+            writer.flush()?;
+        }
+    }
+
+    // The last line might not end with a newline
+    if !partial_line.is_empty() {
+        writer.write_all(&translate(&partial_line))?;
+    }
+    writer.flush()
+}
+
+/// Translates the paths in one line of output of a git subcommand.
+///
+/// The line is passed without its line terminator, and every absolute WSL
+/// path found in it is translated with `translate_path`.
+pub type LineTranslator = fn(line: &str, translate_path: &dyn Fn(&str) -> String) -> String;
+
+/// Output translators for the git subcommands that print paths.
+const LINE_TRANSLATORS: &[(&str, LineTranslator)] = &[
+    ("rev-parse", translate_rev_parse),
+    ("remote", translate_remote),
+    ("worktree", translate_worktree),
+    ("submodule", translate_submodule),
+    ("init", translate_init),
+];
+
+/// Return the output translator for the git subcommand `subcommand`.
+///
+/// Subcommands without a translator of their own get one that only translates
+/// lines consisting of a single absolute path.
+pub fn line_translator(subcommand: &str) -> LineTranslator {
+    LINE_TRANSLATORS
+        .iter()
+        .find(|&&(name, _)| name == subcommand)
+        .map_or(translate_absolute, |&(_, translator)| translator)
+}
+
+/// Translate one record of output, including its terminating `separator`,
+/// with `line_translator`.
+///
+/// Records that are not valid UTF-8 are returned unchanged.
+pub fn translate_line(
+    line: &[u8],
+    separator: u8,
+    line_translator: LineTranslator,
+    translate_path: &dyn Fn(&str) -> String,
+) -> Vec<u8> {
+    let text = match ::std::str::from_utf8(line) {
+        Ok(text) => text,
+        Err(_) => return line.to_vec(),
+    };
+    let content = if separator == b'\n' {
+        text.trim_end_matches(&['\n', '\r'][..])
+    } else {
+        text.trim_end_matches(separator as char)
+    };
+    let mut translated = line_translator(content, translate_path);
+    translated.push_str(&text[content.len()..]);
+    translated.into_bytes()
+}
+
+/// Translate `path` if it is an absolute WSL path.
+fn translate_absolute(path: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    if path.starts_with('/') {
+        translate_path(path)
+    } else {
+        path.to_string()
+    }
+}
+
+/// `git rev-parse --show-toplevel`, `--git-dir`, `--git-common-dir`,
+/// `--absolute-git-dir` etc. print a single path per line. Paths relative to
+/// the working directory are valid on Windows as well.
+fn translate_rev_parse(line: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    translate_absolute(line, translate_path)
+}
+
+/// `git remote -v` prints `<name>\t<url> (fetch)`, `git remote get-url` the
+/// URL alone and `git remote show` an indented `Fetch URL: <url>`.
+fn translate_remote(line: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    lazy_static! {
+        static ref REMOTE_VERBOSE_RE: Regex =
+            Regex::new(r"^(?P<name>[^\t]+\t)(?P<url>.*?)(?P<type> \((fetch|push)\))?$")
+                .expect("Failed to compile REMOTE_VERBOSE_RE regex");
+        static ref REMOTE_SHOW_RE: Regex =
+            Regex::new(r"^(?P<label>\s*(Fetch|Push) +URL: )(?P<url>.*)$")
+                .expect("Failed to compile REMOTE_SHOW_RE regex");
+    }
+
+    for re in [&*REMOTE_VERBOSE_RE, &*REMOTE_SHOW_RE].iter() {
+        if let Some(caps) = re.captures(line) {
+            let url = caps.name("url").unwrap();
+            return format!(
+                "{}{}{}",
+                &line[..url.start()],
+                translate_absolute(url.as_str(), translate_path),
+                &line[url.end()..]
+            );
+        }
+    }
+    translate_absolute(line, translate_path)
+}
+
+/// `git worktree list` prints `<path>  <commit> [<branch>]`, followed by
+/// `(bare)` or `(detached HEAD)` and annotations like `locked`. With
+/// `--porcelain` the path is printed as `worktree <path>`.
+///
+/// The details are matched as a whole at the end of the line, since the path
+/// may contain spaces followed by something that looks like a commit.
+fn translate_worktree(line: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    lazy_static! {
+        static ref WORKTREE_RE: Regex = Regex::new(
+            r"^(?P<path>/.*?)(?P<details> +(\(bare\)|[0-9a-f]{7,} (\[[^\]]*\]|\(detached HEAD\)))( [a-z]+)*)$"
+        )
+        .expect("Failed to compile WORKTREE_RE regex");
+    }
+
+    if let Some(path) = line.strip_prefix("worktree ") {
+        return format!("worktree {}", translate_absolute(path, translate_path));
+    }
+    match WORKTREE_RE.captures(line) {
+        Some(caps) => format!("{}{}", translate_path(&caps["path"]), &caps["details"]),
+        None => line.to_string(),
+    }
+}
+
+/// `git submodule status` prints `<state><commit> <path> (<describe>)`. The
+/// path is relative to the top-level of the repository and only needs to be
+/// translated if it is absolute.
+fn translate_submodule(line: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    lazy_static! {
+        static ref SUBMODULE_STATUS_RE: Regex =
+            Regex::new(r"^(?P<commit>[ +\-U][0-9a-f]{7,} )(?P<path>.*?)(?P<describe> \(.*\))?$")
+                .expect("Failed to compile SUBMODULE_STATUS_RE regex");
+    }
+
+    match SUBMODULE_STATUS_RE.captures(line) {
+        Some(caps) => format!(
+            "{}{}{}",
+            &caps["commit"],
+            translate_absolute(&caps["path"], translate_path),
+            caps.name("describe").map_or("", |m| m.as_str())
+        ),
+        None => line.to_string(),
+    }
+}
+
+/// `git init` prints `Initialized empty Git repository in <path>` or
+/// `Reinitialized existing Git repository in <path>`. The message may be
+/// localized, so the path is taken to start at the first absolute path and end
+/// with the trailing `/` git prints after the repository directory.
+fn translate_init(line: &str, translate_path: &dyn Fn(&str) -> String) -> String {
+    let start = match line.find(" /") {
+        Some(pos) => pos + 1,
+        None => return line.to_string(),
+    };
+    let end = line.rfind('/').unwrap() + 1;
+    format!(
+        "{}{}{}",
+        &line[..start],
+        translate_path(&line[start..end]),
+        &line[end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn to_upper(line: &[u8]) -> Vec<u8> {
+        line.to_ascii_uppercase()
+    }
+
+    fn translate_with_limit(input: &[u8], max_line_length: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        translate_lines_bounded(
+            Cursor::new(input),
+            &mut output,
+            b'\n',
+            max_line_length,
+            to_upper,
+        )
+        .unwrap();
+        output
+    }
+
+    #[test]
+    fn translate_every_line() {
+        let mut lines: Vec<Vec<u8>> = Vec::new();
+        let mut output = Vec::new();
+        translate_lines(
+            Cursor::new(&b"/a\n/b\n\n/c"[..]),
+            &mut output,
+            b'\n',
+            |line: &[u8]| {
+                lines.push(line.to_vec());
+                to_upper(line)
+            },
+        )
+        .unwrap();
+        assert_eq!(output, b"/A\n/B\n\n/C");
+        assert_eq!(
+            lines,
+            vec![
+                b"/a\n".to_vec(),
+                b"/b\n".to_vec(),
+                b"\n".to_vec(),
+                b"/c".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_lines_longer_than_buffer() {
+        // Lines that are split between reads are still translated as a whole,
+        // as long as they are shorter than the limit.
+        let mut input = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..100 {
+            let line = format!("line {} /some/path\n", i);
+            input.extend_from_slice(line.as_bytes());
+            expected.extend_from_slice(line.to_uppercase().as_bytes());
+        }
+        assert_eq!(translate_with_limit(&input, 32), expected);
+    }
+
+    #[test]
+    fn pass_on_too_long_lines() {
+        assert_eq!(
+            translate_with_limit(b"abc\nabcdefghij\nabc", 8),
+            b"ABC\nabcdefghij\nABC"
+        );
+        assert_eq!(translate_with_limit(b"abcdefghij", 8), b"abcdefghij");
+    }
+
+    fn skip(input: &[u8], marker: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let (discarded, mut rest) = skip_until(Cursor::new(input.to_vec()), marker).unwrap();
+        let mut output = Vec::new();
+        rest.read_to_end(&mut output).unwrap();
+        (discarded, output)
+    }
+
+    #[test]
+    fn skip_output_before_marker() {
+        let cases: &[(&[u8], &[u8], &[u8])] = &[
+            (b"@@git output\n", b"", b"git output\n"),
+            (
+                b"Agent pid 123\n@@git output\n",
+                b"Agent pid 123\n",
+                b"git output\n",
+            ),
+            (b"a@b@@@@c", b"a@b", b"@@c"),
+            (b"junk@@", b"junk", b""),
+            (b"no marker\n", b"", b"no marker\n"),
+            (b"", b"", b""),
+        ];
+        for &(input, discarded, output) in cases {
+            assert_eq!(
+                skip(input, b"@@"),
+                (discarded.to_vec(), output.to_vec()),
+                "{}",
+                String::from_utf8_lossy(input)
+            );
+        }
+    }
+
+    #[test]
+    fn skip_large_output_before_marker() {
+        let mut input = vec![b'x'; 10000];
+        input.extend_from_slice(b"@@rest");
+        assert_eq!(skip(&input, b"@@"), (vec![b'x'; 10000], b"rest".to_vec()));
+
+        // Markers that span two reads of 4096 bytes, or start the second
+        for len in 4093..4097 {
+            let mut input = vec![b'x'; len];
+            input.extend_from_slice(b"@@@rest");
+            assert_eq!(
+                skip(&input, b"@@@"),
+                (vec![b'x'; len], b"rest".to_vec()),
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn empty_output() {
+        assert_eq!(translate_with_limit(b"", 8), b"");
+    }
+
+    /// Marks translated paths, so tests do not depend on the mount table.
+    fn mark_path(path: &str) -> String {
+        format!("<{}>", path)
+    }
+
+    fn check_translator(subcommand: &str, cases: &[(&str, &str)]) {
+        let translator = line_translator(subcommand);
+        for &(line, expected) in cases {
+            assert_eq!(translator(line, &mark_path), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn other_subcommands_translate_absolute_paths() {
+        check_translator(
+            "ls-files",
+            &[
+                ("/home/me/repo/file.txt", "</home/me/repo/file.txt>"),
+                ("src/main.rs", "src/main.rs"),
+                ("M  /home/me/repo/file.txt", "M  /home/me/repo/file.txt"),
+            ],
+        );
+    }
+
+    #[test]
+    fn translate_line_keeps_line_terminator() {
+        let translator = line_translator("rev-parse");
+        let cases: &[(&[u8], &[u8])] = &[
+            (b"/home/me/repo\n", b"</home/me/repo>\n"),
+            (b"/home/me/repo\r\n", b"</home/me/repo>\r\n"),
+            (b"/home/me/repo", b"</home/me/repo>"),
+            (b"\n", b"\n"),
+            (b"/home/\xff\n", b"/home/\xff\n"),
+        ];
+        for &(line, expected) in cases {
+            assert_eq!(
+                translate_line(line, b'\n', translator, &mark_path),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn separator_from_arguments() {
+        let cases: &[(&[&str], u8)] = &[
+            (&["worktree", "list", "--porcelain", "-z"], b'\0'),
+            (&["ls-files", "--null"], b'\0'),
+            (&["ls-files", "-0"], b'\0'),
+            (&["rev-parse", "--show-toplevel"], b'\n'),
+            (&["log", "--", "-z"], b'\n'),
+        ];
+        for &(args, expected) in cases {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            assert_eq!(record_separator(&args), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn translate_nul_separated_records() {
+        let translator = line_translator("worktree");
+        let input = b"worktree /home/me/repo\nfile\0HEAD 4b825dc\0branch refs/heads/main\0\0worktree /tmp/x";
+        let mut output = Vec::new();
+        translate_lines(Cursor::new(&input[..]), &mut output, b'\0', |record| {
+            translate_line(record, b'\0', translator, &mark_path)
+        })
+        .unwrap();
+        assert_eq!(
+            &output[..],
+            &b"worktree </home/me/repo\nfile>\0HEAD 4b825dc\0branch refs/heads/main\0\0worktree </tmp/x>"[..]
+        );
+    }
+
+    #[test]
+    fn rev_parse_output() {
+        check_translator(
+            "rev-parse",
+            &[
+                ("/home/me/repo", "</home/me/repo>"),
+                ("/home/me/my repo/.git", "</home/me/my repo/.git>"),
+                (".git", ".git"),
+                ("sub/dir/", "sub/dir/"),
+                (
+                    "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+                    "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+                ),
+                ("", ""),
+            ],
+        );
+    }
+
+    #[test]
+    fn remote_output() {
+        check_translator(
+            "remote",
+            &[
+                ("origin", "origin"),
+                (
+                    "origin\t/home/me/repo (fetch)",
+                    "origin\t</home/me/repo> (fetch)",
+                ),
+                (
+                    "mirror\t/tmp/wslgit test (1) (push)",
+                    "mirror\t</tmp/wslgit test (1)> (push)",
+                ),
+                (
+                    "origin\thttps://github.com/andy-5/wslgit.git (fetch)",
+                    "origin\thttps://github.com/andy-5/wslgit.git (fetch)",
+                ),
+                (
+                    "origin\tgit@github.com:andy-5/wslgit.git (push)",
+                    "origin\tgit@github.com:andy-5/wslgit.git (push)",
+                ),
+                ("/home/me/repo", "</home/me/repo>"),
+                ("  Fetch URL: /home/me/repo", "  Fetch URL: </home/me/repo>"),
+                ("  Push  URL: /home/me/repo", "  Push  URL: </home/me/repo>"),
+                ("  HEAD branch: main", "  HEAD branch: main"),
+            ],
+        );
+    }
+
+    #[test]
+    fn worktree_output() {
+        check_translator(
+            "worktree",
+            &[
+                (
+                    "/home/me/repo          4b825dc [main]",
+                    "</home/me/repo>          4b825dc [main]",
+                ),
+                (
+                    "/home/me/my repo 2     4b825dc (detached HEAD) locked",
+                    "</home/me/my repo 2>     4b825dc (detached HEAD) locked",
+                ),
+                ("/home/me/bare.git  (bare)", "</home/me/bare.git>  (bare)"),
+                (
+                    "/home/me/x abcdef1 dir  4b825dc [main]",
+                    "</home/me/x abcdef1 dir>  4b825dc [main]",
+                ),
+                (
+                    "/home/me/x abcdef1 [y]  4b825dc [fix] locked prunable",
+                    "</home/me/x abcdef1 [y]>  4b825dc [fix] locked prunable",
+                ),
+                ("worktree /home/me/repo", "worktree </home/me/repo>"),
+                (
+                    "HEAD 4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+                    "HEAD 4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+                ),
+                ("branch refs/heads/main", "branch refs/heads/main"),
+                ("locked /home/me is gone", "locked /home/me is gone"),
+                ("bare", "bare"),
+            ],
+        );
+    }
+
+    #[test]
+    fn submodule_output() {
+        check_translator(
+            "submodule",
+            &[
+                (
+                    " 4b825dc642cb6eb9a060e54bf8d69288fbee4904 lib/sub (v1.0)",
+                    " 4b825dc642cb6eb9a060e54bf8d69288fbee4904 lib/sub (v1.0)",
+                ),
+                (
+                    "+4b825dc642cb6eb9a060e54bf8d69288fbee4904 /home/me/sub (heads/main)",
+                    "+4b825dc642cb6eb9a060e54bf8d69288fbee4904 </home/me/sub> (heads/main)",
+                ),
+                (
+                    "-4b825dc642cb6eb9a060e54bf8d69288fbee4904 /home/me/sub",
+                    "-4b825dc642cb6eb9a060e54bf8d69288fbee4904 </home/me/sub>",
+                ),
+                ("Entering 'lib/sub'", "Entering 'lib/sub'"),
+            ],
+        );
+    }
+
+    #[test]
+    fn init_output() {
+        check_translator(
+            "init",
+            &[
+                (
+                    "Initialized empty Git repository in /home/me/repo/.git/",
+                    "Initialized empty Git repository in </home/me/repo/.git/>",
+                ),
+                (
+                    "Reinitialized existing shared Git repository in /home/me/my repo/.git/",
+                    "Reinitialized existing shared Git repository in </home/me/my repo/.git/>",
+                ),
+                (
+                    "Leeres Git-Repository in /home/me/repo/.git/ initialisiert",
+                    "Leeres Git-Repository in </home/me/repo/.git/> initialisiert",
+                ),
+                (
+                    "hint: Using 'master' as the name",
+                    "hint: Using 'master' as the name",
+                ),
+            ],
+        );
+    }
+}
@@ -0,0 +1,126 @@
+/// Quote `arg` for a POSIX shell so that the shell passes it on unchanged.
+///
+/// The argument is wrapped in single quotes, inside which no character has a
+/// special meaning. A single quote is written as `'\''`, i.e. close the quoted
+/// string, add an escaped single quote and open a new quoted string.
+pub fn quote(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('\'');
+    for ch in arg.chars() {
+        if ch == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(ch);
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Build a shell command line that executes `args` as a command, with every
+/// argument passed on unchanged.
+pub fn command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use std::process::Command;
+
+    /// Let bash execute `printf` with the quoted `args`, and return the
+    /// arguments as seen by `printf`.
+    ///
+    /// Returns `None` if bash is not available.
+    fn round_trip(args: &[String]) -> Option<Vec<String>> {
+        let mut cmd: Vec<String> = vec!["printf".into(), "%s\\0".into()];
+        cmd.extend_from_slice(args);
+        let output = Command::new("bash")
+            .arg("-c")
+            .arg(command_line(&cmd))
+            .output()
+            .ok()?;
+        assert!(output.status.success());
+        let mut received: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .split('\0')
+            .map(|arg| arg.to_string())
+            .collect();
+        // printf prints the format string once even without arguments
+        received.pop();
+        if args.is_empty() {
+            received.clear();
+        }
+        Some(received)
+    }
+
+    #[test]
+    fn quote_arguments() {
+        let cases: &[(&str, &str)] = &[
+            ("", "''"),
+            ("abc", "'abc'"),
+            ("abc def", "'abc def'"),
+            ("ab\ncd", "'ab\ncd'"),
+            ("user.(name|email)", "'user.(name|email)'"),
+            ("--pretty=format:\"(X|Y)\"", "'--pretty=format:\"(X|Y)\"'"),
+            ("<!--RevisionMessageEnd-->", "'<!--RevisionMessageEnd-->'"),
+            ("$(rm -rf ~)", "'$(rm -rf ~)'"),
+            ("`id`; echo & wait", "'`id`; echo & wait'"),
+            ("it's", "'it'\\''s'"),
+            ("''", "''\\'''\\'''"),
+            ("\\", "'\\'"),
+        ];
+        for &(arg, expected) in cases {
+            assert_eq!(quote(arg), expected);
+        }
+    }
+
+    #[test]
+    fn build_command_line() {
+        let args: Vec<String> = vec!["git".into(), "log".into(), "--format=%H %s".into()];
+        assert_eq!(command_line(&args), "'git' 'log' '--format=%H %s'");
+    }
+
+    #[test]
+    fn shell_round_trip() {
+        let args: Vec<String> = vec![
+            "".into(),
+            " ".into(),
+            "--pretty=format:$(rm -rf ~)".into(),
+            "`rm -rf ~`".into(),
+            "a;b&c|d>e<f".into(),
+            "${HOME}$HOME$1$@$*$$$?$!".into(),
+            "'\"'\"'".into(),
+            "\\'\\".into(),
+            "!!:$ !-1".into(),
+            "*?[a-z]~{a,b}".into(),
+            "#comment".into(),
+            "line1\nline2\r\n\t".into(),
+            "±ünïcødé 🦀".into(),
+        ];
+        match round_trip(&args) {
+            Some(received) => assert_eq!(received, args),
+            None => print!("SKIPPING TEST ... "),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn shell_round_trip_random_arguments(args in vec("[^\\x00]*", 0..8)) {
+            if let Some(received) = round_trip(&args) {
+                prop_assert_eq!(received, args);
+            }
+        }
+
+        #[test]
+        fn shell_round_trip_shell_characters(args in vec("[ -~\\t\\n]*", 0..8)) {
+            if let Some(received) = round_trip(&args) {
+                prop_assert_eq!(received, args);
+            }
+        }
+    }
+}
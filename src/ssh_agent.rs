@@ -0,0 +1,162 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+
+/// Option of `wslgit.exe` that relays one connection of an ssh client inside
+/// WSL, on stdin and stdout, to the named pipe of the Windows ssh-agent.
+pub const RELAY_OPTION: &str = "--ssh-agent-relay";
+
+/// File name of the script next to `wslgit.exe` that starts the relay inside
+/// WSL and runs git with `SSH_AUTH_SOCK` set.
+pub const SHIM_NAME: &str = "wslgit-ssh-agent";
+
+/// Largest message of the ssh-agent protocol, as in OpenSSH.
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// Read a message of the ssh-agent protocol, a 32-bit big-endian length
+/// followed by that many bytes, and return its contents.
+///
+/// Returns `None` if `input` ends before a message starts.
+pub fn read_message<R: Read>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match input.read(&mut len[read..])? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => read += n,
+        }
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ssh-agent message of {} bytes is too long", len),
+        ));
+    }
+    let mut message = vec![0; len];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+/// Write `message` with its length as a message of the ssh-agent protocol.
+pub fn write_message<W: Write>(output: &mut W, message: &[u8]) -> io::Result<()> {
+    output.write_all(&(message.len() as u32).to_be_bytes())?;
+    output.write_all(message)?;
+    output.flush()
+}
+
+/// Relay every request of the client from `input` to `agent`, and the
+/// response of the agent to `output`, until the client closes `input`.
+///
+/// Returns the number of relayed requests.
+pub fn relay<R: Read, A: Read + Write, W: Write>(
+    input: &mut R,
+    agent: &mut A,
+    output: &mut W,
+) -> io::Result<usize> {
+    let mut requests = 0;
+    while let Some(request) = read_message(input)? {
+        write_message(agent, &request)?;
+        let response = read_message(agent)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the ssh-agent closed the connection",
+            )
+        })?;
+        write_message(output, &response)?;
+        requests += 1;
+    }
+    Ok(requests)
+}
+
+/// Connect to the ssh-agent at the named pipe `pipe` and relay the requests
+/// from stdin to it.
+pub fn run(pipe: &str) -> Result<usize, String> {
+    let mut agent = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(pipe)
+        .map_err(|e| format!("failed to connect to the ssh-agent at {}: {}", pipe, e))?;
+    relay(&mut io::stdin(), &mut agent, &mut io::stdout())
+        .map_err(|e| format!("failed to relay to the ssh-agent at {}: {}", pipe, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(contents: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        write_message(&mut message, contents).unwrap();
+        message
+    }
+
+    #[test]
+    fn message_framing() {
+        // SSH_AGENTC_REQUEST_IDENTITIES
+        assert_eq!(message(&[11]), vec![0, 0, 0, 1, 11]);
+        assert_eq!(message(&[]), vec![0, 0, 0, 0]);
+
+        let mut input = &[message(&[11]), message(&[13, 1, 2])].concat()[..];
+        assert_eq!(read_message(&mut input).unwrap(), Some(vec![11]));
+        assert_eq!(read_message(&mut input).unwrap(), Some(vec![13, 1, 2]));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let truncated: &[&[u8]] = &[&[0, 0], &[0, 0, 0, 2, 11]];
+        for &input in truncated {
+            assert_eq!(
+                read_message(&mut &input[..]).unwrap_err().kind(),
+                io::ErrorKind::UnexpectedEof
+            );
+        }
+        assert_eq!(
+            read_message(&mut &[0, 4, 0, 1][..]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relay_to_agent() {
+        use std::os::unix::net::UnixStream;
+        use std::thread;
+
+        // A stand-in for the agent that answers every request with its
+        // reversed contents
+        let (mut agent, mut agent_end) = UnixStream::pair().unwrap();
+        let stand_in = thread::spawn(move || {
+            while let Some(mut request) = read_message(&mut agent_end).unwrap() {
+                request.reverse();
+                write_message(&mut agent_end, &request).unwrap();
+            }
+        });
+
+        let requests = [message(&[11]), message(&[13, 1, 2])].concat();
+        let mut output = Vec::new();
+        assert_eq!(
+            relay(&mut &requests[..], &mut agent, &mut output).unwrap(),
+            2
+        );
+        assert_eq!(output, [message(&[11]), message(&[2, 1, 13])].concat());
+
+        drop(agent);
+        stand_in.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn agent_closes_connection() {
+        use std::net::Shutdown;
+        use std::os::unix::net::UnixStream;
+
+        let (mut agent, agent_end) = UnixStream::pair().unwrap();
+        agent_end.shutdown(Shutdown::Both).unwrap();
+        let error = relay(&mut &message(&[11])[..], &mut agent, &mut Vec::new()).unwrap_err();
+        assert!(
+            error.kind() == io::ErrorKind::UnexpectedEof
+                || error.kind() == io::ErrorKind::BrokenPipe,
+            "{:?}",
+            error
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use mounts::MountTable;
+use unc;
+
+/// Translates paths between their Windows and WSL representations without
+/// spawning any process.
+///
+/// Only paths whose translation is unambiguous are handled, everything else
+/// returns `None` and must be passed on to `wslpath` inside WSL.
+pub struct PathTranslator {
+    /// Where the Windows drives and network shares are mounted inside WSL.
+    mounts: MountTable,
+    /// UNC prefix of the WSL distribution git runs in, e.g. `\\wsl$\Ubuntu`.
+    dist_share: Option<String>,
+}
+
+/// Build the UNC prefix of the distribution `dist_name`, e.g.
+/// `\\wsl.localhost\Ubuntu`.
+///
+/// The server of `working_dir` is reused if it is a path into the same
+/// distribution, so that translated output matches the paths the caller uses.
+pub fn dist_share(working_dir: &str, dist_name: &str) -> String {
+    let server = match unc::parse(working_dir) {
+        Ok(unc_path) if unc_path.dist.eq_ignore_ascii_case(dist_name) => unc_path.server,
+        _ => "wsl.localhost",
+    };
+    format!("\\\\{}\\{}", server, dist_name)
+}
+
+fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+impl PathTranslator {
+    /// * `mounts` - Mount table of the distribution.
+    /// * `dist_share` - UNC prefix of the distribution, e.g. `\\wsl$\Ubuntu`,
+    ///   or `None` if it is not known.
+    pub fn new(mounts: MountTable, dist_share: Option<String>) -> PathTranslator {
+        PathTranslator { mounts, dist_share }
+    }
+
+    /// UNC prefix of the WSL distribution git runs in, if known.
+    pub fn dist_share(&self) -> Option<&str> {
+        self.dist_share.as_deref()
+    }
+
+    /// Name of the WSL distribution git runs in, if known.
+    pub fn dist_name(&self) -> Option<&str> {
+        self.dist_share
+            .as_ref()
+            .and_then(|share| unc::parse(share).ok())
+            .map(|unc_path| unc_path.dist)
+    }
+
+    /// Check that `path` is not a UNC path into another distribution than
+    /// the one git runs in, since git cannot access such paths.
+    ///
+    /// Always succeeds if the distribution git runs in is not known.
+    pub fn check_dist(&self, path: &str) -> Result<(), String> {
+        match (unc::parse(path), self.dist_name()) {
+            (Ok(unc_path), Some(name)) if !name.eq_ignore_ascii_case(unc_path.dist) => {
+                Err(format!(
+                    "{} is in the WSL distribution {}, but git runs in {}",
+                    path, unc_path.dist, name
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return `false` if git cannot access the absolute Windows path `path`,
+    /// because it is on a drive or network share that is not mounted in the
+    /// distribution, or in another distribution that is not mounted.
+    ///
+    /// Returns `true` for relative paths, and when in doubt.
+    pub fn is_reachable(&self, path: &str) -> bool {
+        let bytes = path.as_bytes();
+        let is_drive_path = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && is_separator(bytes[2] as char);
+        let is_unc_path = path.starts_with("\\\\") || path.starts_with("//");
+        if !is_drive_path && !is_unc_path {
+            return true;
+        }
+        match (unc::parse(path), self.dist_name()) {
+            (Ok(unc_path), Some(name)) if name.eq_ignore_ascii_case(unc_path.dist) => true,
+            (Ok(_), _) => self.to_unix(path).is_some(),
+            _ => self.mounts.is_mounted(path),
+        }
+    }
+
+    /// Translate an absolute Windows path to a WSL path.
+    ///
+    /// Handles `C:\Foo`, `C:/Foo`, UNC paths into the distribution that git
+    /// runs in and network shares mounted in it, including other
+    /// distributions mounted as `\\wsl$\dist`. Returns `None` if the path cannot be translated without help
+    /// from `wslpath`.
+    pub fn to_unix(&self, path: &str) -> Option<String> {
+        let bytes = path.as_bytes();
+        let is_drive_path = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && is_separator(bytes[2] as char);
+        if is_drive_path {
+            return self.mounts.to_unix(path);
+        }
+
+        if let Ok(unc_path) = unc::parse(path) {
+            match self.dist_name() {
+                Some(name) if name.eq_ignore_ascii_case(unc_path.dist) => {
+                    let rest = unc_path.path.replace('\\', "/");
+                    return Some(if rest.is_empty() {
+                        "/".to_string()
+                    } else {
+                        rest
+                    });
+                }
+                // Another distribution, unless it is mounted with drvfs
+                _ => {
+                    return unc::WSL_SERVERS.iter().find_map(|server| {
+                        self.mounts.to_unix(&format!(
+                            "\\\\{}\\{}{}",
+                            server, unc_path.dist, unc_path.path
+                        ))
+                    })
+                }
+            }
+        }
+
+        // Mapped network shares
+        self.mounts.to_unix(path)
+    }
+
+    /// Translate an absolute WSL path to a Windows path.
+    ///
+    /// Paths below mounted drives or network shares become Windows paths, all
+    /// other paths become UNC paths into the distribution. Returns `None` if the path
+    /// cannot be translated without help from `wslpath`.
+    pub fn to_win(&self, path: &str) -> Option<String> {
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        if let Some(win_path) = self.mounts.to_win(path) {
+            return Some(win_path);
+        }
+
+        match self.dist_share {
+            Some(ref share) if path == "/" => Some(format!("{}\\", share)),
+            Some(ref share) => Some(format!("{}{}", share, path.replace('/', "\\"))),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mounts::Mount;
+
+    fn mounts(automount_root: &str) -> MountTable {
+        MountTable::new(automount_root, Vec::new())
+    }
+
+    #[test]
+    fn win_to_unix() {
+        let translator = PathTranslator::new(mounts("/mnt/"), Some(r"\\wsl$\Ubuntu".to_string()));
+        let cases: &[(&str, Option<&str>)] = &[
+            (r"C:\Foo", Some("/mnt/c/Foo")),
+            (r"C:/Foo", Some("/mnt/c/Foo")),
+            (r"d:\Foo\bar baz.txt", Some("/mnt/d/Foo/bar baz.txt")),
+            (r"C:\Foo\", Some("/mnt/c/Foo/")),
+            (r"C:\", Some("/mnt/c/")),
+            (r"C:/", Some("/mnt/c/")),
+            (r"\\wsl$\Ubuntu\home\me", Some("/home/me")),
+            (r"\\wsl$\ubuntu\home\me", Some("/home/me")),
+            (r"\\wsl.localhost\Ubuntu\home\me", Some("/home/me")),
+            ("//wsl$/Ubuntu/home/me", Some("/home/me")),
+            (r"\\?\UNC\wsl$\Ubuntu\home\me", Some("/home/me")),
+            (r"\\wsl$\Ubuntu", Some("/")),
+            (r"\\wsl$\Ubuntu\", Some("/")),
+            (r"\\wsl$\Debian\home\me", None),
+            (r"\\server\share\file.txt", None),
+            ("C:", None),
+            ("C:foo", None),
+            ("/mnt/c/Foo", None),
+            (r"relative\path", None),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(
+                translator.to_unix(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn paths_into_other_distributions() {
+        let translator = PathTranslator::new(mounts("/mnt/"), Some(r"\\wsl$\Ubuntu".to_string()));
+        assert_eq!(translator.check_dist(r"\\wsl$\Ubuntu\home"), Ok(()));
+        assert_eq!(translator.check_dist(r"\\wsl.localhost\ubuntu"), Ok(()));
+        assert_eq!(translator.check_dist(r"C:\Foo"), Ok(()));
+        assert_eq!(translator.check_dist(r"\\server\share"), Ok(()));
+        assert_eq!(
+            translator.check_dist("//wsl$/Debian/home"),
+            Err(
+                "//wsl$/Debian/home is in the WSL distribution Debian, but git runs in Ubuntu"
+                    .to_string()
+            )
+        );
+
+        let translator = PathTranslator::new(mounts("/mnt/"), None);
+        assert_eq!(translator.check_dist(r"\\wsl$\Debian\home"), Ok(()));
+    }
+
+    #[test]
+    fn win_to_unix_custom_root() {
+        let translator = PathTranslator::new(mounts("/"), None);
+        let cases: &[(&str, Option<&str>)] = &[
+            (r"C:\Foo", Some("/c/Foo")),
+            (r"E:/Foo/bar", Some("/e/Foo/bar")),
+            (r"\\wsl$\Ubuntu\home\me", None),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(
+                translator.to_unix(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn network_shares() {
+        let mounts = MountTable::new(
+            "/mnt/",
+            vec![Mount {
+                source: r"\\server\share".to_string(),
+                target: "/mnt/share".to_string(),
+            }],
+        );
+        let translator = PathTranslator::new(mounts, None);
+        assert_eq!(
+            translator.to_unix(r"\\server\share\repo"),
+            Some("/mnt/share/repo".to_string())
+        );
+        assert_eq!(translator.to_unix(r"\\server\other\repo"), None);
+        assert_eq!(
+            translator.to_win("/mnt/share/repo"),
+            Some(r"\\server\share\repo".to_string())
+        );
+    }
+
+    #[test]
+    fn mounted_distributions() {
+        let mounts = MountTable::new(
+            "/mnt/",
+            vec![Mount {
+                source: r"\\wsl.localhost\Debian".to_string(),
+                target: "/mnt/debian".to_string(),
+            }],
+        );
+        let translator = PathTranslator::new(mounts, Some(r"\\wsl$\Ubuntu".to_string()));
+        let cases: &[(&str, Option<&str>)] = &[
+            (
+                r"\\wsl.localhost\Debian\tmp\msg.txt",
+                Some("/mnt/debian/tmp/msg.txt"),
+            ),
+            (
+                r"\\wsl$\debian\tmp\msg.txt",
+                Some("/mnt/debian/tmp/msg.txt"),
+            ),
+            ("//wsl$/Debian/tmp", Some("/mnt/debian/tmp")),
+            (r"\\wsl$\Alpine\tmp", None),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(
+                translator.to_unix(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            translator.to_win("/mnt/debian/tmp"),
+            Some(r"\\wsl.localhost\Debian\tmp".to_string())
+        );
+    }
+
+    #[test]
+    fn reachable_paths() {
+        let mounts = MountTable::new(
+            "/mnt/",
+            vec![Mount {
+                source: "C:".to_string(),
+                target: "/mnt/c".to_string(),
+            }],
+        );
+        let translator = PathTranslator::new(mounts, Some(r"\\wsl$\Ubuntu".to_string()));
+        let cases: &[(&str, bool)] = &[
+            (r"C:\Users\me\AppData\Local\Temp\msg", true),
+            (r"D:\Temp\msg", false),
+            (r"\\wsl$\Ubuntu\tmp\msg", true),
+            (r"\\wsl$\Debian\tmp\msg", false),
+            (r"\\server\share\msg", false),
+            ("msg.txt", true),
+            ("-", true),
+        ];
+        for &(path, expected) in cases {
+            assert_eq!(translator.is_reachable(path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn dist_share_from_working_dir() {
+        let cases: &[(&str, &str, &str)] = &[
+            (r"\\wsl$\Ubuntu\repo", "Ubuntu", r"\\wsl$\Ubuntu"),
+            (
+                r"\\wsl.localhost\Ubuntu\repo",
+                "Ubuntu",
+                r"\\wsl.localhost\Ubuntu",
+            ),
+            (r"\\wsl$\Ubuntu\repo", "Debian", r"\\wsl.localhost\Debian"),
+            (r"C:\repo", "Debian", r"\\wsl.localhost\Debian"),
+        ];
+        for &(working_dir, dist_name, expected) in cases {
+            assert_eq!(dist_share(working_dir, dist_name), expected);
+        }
+    }
+
+    #[test]
+    fn unix_to_win() {
+        let translator = PathTranslator::new(mounts("/mnt"), Some(r"\\wsl$\Ubuntu".to_string()));
+        let cases: &[(&str, Option<&str>)] = &[
+            ("/mnt/c/Foo", Some(r"C:\Foo")),
+            ("/mnt/c/Foo/bar baz.txt", Some(r"C:\Foo\bar baz.txt")),
+            ("/mnt/c/", Some(r"C:\")),
+            ("/mnt/c", Some(r"C:\")),
+            ("/mnt/d/Foo/", Some(r"D:\Foo\")),
+            ("/mnt/wsl/shared", Some(r"\\wsl$\Ubuntu\mnt\wsl\shared")),
+            ("/home/me/repo", Some(r"\\wsl$\Ubuntu\home\me\repo")),
+            ("/", Some(r"\\wsl$\Ubuntu\")),
+            ("relative/path", None),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(
+                translator.to_win(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn unix_to_win_unknown_dist() {
+        let translator = PathTranslator::new(mounts("/"), None);
+        let cases: &[(&str, Option<&str>)] = &[
+            ("/c/Foo", Some(r"C:\Foo")),
+            ("/c", Some(r"C:\")),
+            ("/home/me/repo", None),
+            ("/mnt/c/Foo", None),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(
+                translator.to_win(input),
+                expected.map(|s| s.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+}
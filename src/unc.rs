@@ -0,0 +1,153 @@
+use std::fmt;
+
+/// Servers of the UNC paths into WSL distributions.
+pub const WSL_SERVERS: &[&str] = &["wsl$", "wsl.localhost"];
+
+/// Prefixes of UNC paths in the long path form, e.g. `\\?\UNC\wsl$\Ubuntu`.
+const LONG_UNC_PREFIXES: &[&str] = &[r"\\?\UNC\", "//?/UNC/"];
+
+/// A UNC path into the filesystem of a WSL distribution, e.g.
+/// `\\wsl$\Ubuntu\home\me`.
+#[derive(Debug, PartialEq)]
+pub struct WslPath<'a> {
+    /// `wsl$` or `wsl.localhost`, as written in the path.
+    pub server: &'a str,
+    /// Name of the distribution.
+    pub dist: &'a str,
+    /// The path inside the distribution, empty or starting with a separator.
+    pub path: &'a str,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UncError {
+    /// The path is not a UNC path into a WSL distribution.
+    NotWslPath,
+    /// The path names the WSL server but no distribution, e.g. `\\wsl$\`.
+    MissingDistribution,
+}
+
+impl fmt::Display for UncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UncError::NotWslPath => write!(f, "not a path into a WSL distribution"),
+            UncError::MissingDistribution => {
+                write!(f, "the path does not name a WSL distribution")
+            }
+        }
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// Parse a UNC path into a WSL distribution.
+///
+/// Accepts `\\wsl$\dist\path` and `\\wsl.localhost\dist\path` with `\` or
+/// `/` as separators, the long form `\\?\UNC\wsl$\dist\path`, and the
+/// distribution root itself, e.g. `\\wsl$\dist`. The server is compared
+/// case-insensitively.
+pub fn parse(path: &str) -> Result<WslPath<'_>, UncError> {
+    let without_prefix = LONG_UNC_PREFIXES
+        .iter()
+        .find_map(|prefix| {
+            path.get(..prefix.len())
+                .filter(|start| start.eq_ignore_ascii_case(prefix))
+                .map(|_| &path[prefix.len()..])
+        })
+        .or_else(|| {
+            let mut chars = path.chars();
+            match (chars.next(), chars.next()) {
+                (Some(a), Some(b)) if is_separator(a) && is_separator(b) => Some(&path[2..]),
+                _ => None,
+            }
+        })
+        .ok_or(UncError::NotWslPath)?;
+
+    let server_end = without_prefix
+        .find(is_separator)
+        .unwrap_or(without_prefix.len());
+    let server = &without_prefix[..server_end];
+    if !WSL_SERVERS.iter().any(|s| s.eq_ignore_ascii_case(server)) {
+        return Err(UncError::NotWslPath);
+    }
+
+    let share_and_path = without_prefix[server_end..].trim_start_matches(is_separator);
+    let dist_end = share_and_path
+        .find(is_separator)
+        .unwrap_or(share_and_path.len());
+    let dist = &share_and_path[..dist_end];
+    if dist.is_empty() {
+        return Err(UncError::MissingDistribution);
+    }
+    Ok(WslPath {
+        server,
+        dist,
+        path: &share_and_path[dist_end..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wsl_paths() {
+        let cases: &[(&str, (&str, &str, &str))] = &[
+            (r"\\wsl$\Ubuntu\home\me", ("wsl$", "Ubuntu", r"\home\me")),
+            (
+                r"\\wsl.localhost\Ubuntu\home",
+                ("wsl.localhost", "Ubuntu", r"\home"),
+            ),
+            (r"\\WSL$\Ubuntu\home", ("WSL$", "Ubuntu", r"\home")),
+            (
+                r"\\WSL.LocalHost\Ubuntu\home",
+                ("WSL.LocalHost", "Ubuntu", r"\home"),
+            ),
+            ("//wsl$/Ubuntu/home/me", ("wsl$", "Ubuntu", "/home/me")),
+            (r"\/wsl$\Ubuntu/home", ("wsl$", "Ubuntu", "/home")),
+            (r"\\?\UNC\wsl$\Ubuntu\home", ("wsl$", "Ubuntu", r"\home")),
+            (
+                r"\\?\unc\wsl.localhost\Ubuntu\home",
+                ("wsl.localhost", "Ubuntu", r"\home"),
+            ),
+            ("//?/UNC/wsl$/Ubuntu/home", ("wsl$", "Ubuntu", "/home")),
+            (r"\\wsl$\Ubuntu", ("wsl$", "Ubuntu", "")),
+            (r"\\wsl$\Ubuntu\", ("wsl$", "Ubuntu", r"\")),
+            (r"\\wsl$\Ubuntu-20.04\", ("wsl$", "Ubuntu-20.04", r"\")),
+        ];
+        for &(path, (server, dist, rest)) in cases {
+            assert_eq!(
+                parse(path),
+                Ok(WslPath {
+                    server,
+                    dist,
+                    path: rest
+                }),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let cases: &[(&str, UncError)] = &[
+            (r"\\wsl$", UncError::MissingDistribution),
+            (r"\\wsl$\", UncError::MissingDistribution),
+            ("//wsl.localhost/", UncError::MissingDistribution),
+            (r"\\?\UNC\wsl$", UncError::MissingDistribution),
+            (r"\\server\share\path", UncError::NotWslPath),
+            (r"\\wsl$x\Ubuntu", UncError::NotWslPath),
+            (r"\\?\C:\path", UncError::NotWslPath),
+            (r"\\", UncError::NotWslPath),
+            (r"C:\wsl$\Ubuntu", UncError::NotWslPath),
+            ("/home/me", UncError::NotWslPath),
+            ("", UncError::NotWslPath),
+            ("\\\u{e9}", UncError::NotWslPath),
+        ];
+        for &(path, ref expected) in cases {
+            assert_eq!(parse(path).as_ref(), Err(expected), "{}", path);
+        }
+    }
+}
@@ -1,4 +1,111 @@
 use std::env;
+use std::fmt;
+use std::process::Command;
+use std::time::Duration;
+
+use cache;
+
+/// How long the list of installed distributions is cached.
+const DISTRIBUTIONS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// An installed WSL distribution, as listed by `wsl --list --verbose`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub name: String,
+    /// E.g. `Running` or `Stopped`, may be localized.
+    pub state: String,
+    /// WSL version, 1 or 2.
+    pub version: u32,
+    pub is_default: bool,
+}
+
+/// A variable in `WSLENV` and its flags, e.g. `p` to translate a path.
+#[derive(Debug, Clone, PartialEq)]
+struct WslEnvEntry {
+    name: String,
+    flags: String,
+}
+
+/// The variables shared between Windows and WSL in `WSLENV`, e.g.
+/// `WSLGIT:GIT_DIR/p`, in their original order.
+///
+/// Every variable is listed once, with the flags of its last entry. Names are
+/// compared case-insensitively, like the names of Windows environment
+/// variables.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WslEnv {
+    entries: Vec<WslEnvEntry>,
+}
+
+impl WslEnv {
+    /// Parse the value of `WSLENV`. Empty entries are ignored.
+    pub fn parse(wslenv: &str) -> WslEnv {
+        let mut parsed = WslEnv::default();
+        for entry in wslenv.split(':').filter(|entry| !entry.is_empty()) {
+            let (name, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            if !name.is_empty() {
+                parsed.set(name, flags);
+            }
+        }
+        parsed
+    }
+
+    /// The current `WSLENV` of this process.
+    pub fn from_env() -> WslEnv {
+        env::var("WSLENV")
+            .map(|wslenv| WslEnv::parse(&wslenv))
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.flags(name).is_some()
+    }
+
+    /// The flags of the variable `name`, `None` if it is not shared.
+    pub fn flags(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+            .map(|entry| entry.flags.as_str())
+    }
+
+    /// Share the variable `name` with `flags`, replacing the flags if it is
+    /// already shared.
+    pub fn set(&mut self, name: &str, flags: &str) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+        {
+            Some(entry) => entry.flags = flags.to_string(),
+            None => self.entries.push(WslEnvEntry {
+                name: name.to_string(),
+                flags: flags.to_string(),
+            }),
+        }
+    }
+
+    /// Set `WSLENV` of this process, and of the processes it starts, to the
+    /// shared variables.
+    pub fn apply(&self) {
+        env::set_var("WSLENV", self.to_string());
+    }
+}
+
+impl fmt::Display for WslEnv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{}", entry.name)?;
+            if !entry.flags.is_empty() {
+                write!(f, "/{}", entry.flags)?;
+            }
+        }
+        Ok(())
+    }
+}
 
 /// Share a value to WSL by using an environment variable and `WSLENV`.
 ///
@@ -7,42 +114,323 @@ use std::env;
 /// * `translate_path` - If `true` will append `/p` to the variable name when added to `WSLENV`.
 pub fn share_val(key: &str, value: &str, translate_path: bool) {
     env::set_var(key, value);
+    share_var(key, if translate_path { "p" } else { "" });
+}
+
+/// Share the existing environment variable `key` to WSL by adding it to
+/// `WSLENV`, or by replacing its flags if it is already listed there.
+///
+/// * `flags` - The `WSLENV` flags of the variable, e.g. `p` or `l`, or an
+///   empty string.
+pub fn share_var(key: &str, flags: &str) {
+    let mut wslenv = WslEnv::from_env();
+    wslenv.set(key, flags);
+    wslenv.apply();
+}
 
-    let wslenv_key = if translate_path {
-        format!("{}/p", key)
+/// Translate `path` by running `wslpath` inside the WSL distribution `dist`.
+///
+/// * `flag` - The `wslpath` flag selecting the kind of translation, e.g. `-w`.
+///
+/// Returns `None` if `wslpath` could not be executed or failed.
+pub fn wslpath(dist: Option<&str>, flag: &str, path: &str) -> Option<String> {
+    let mut cmd = Command::new("wsl");
+    if let Some(dist) = dist {
+        cmd.arg("--distribution").arg(dist);
+    }
+    let output = cmd
+        .arg("-e")
+        .arg("wslpath")
+        .arg(flag)
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let translated = String::from_utf8(output.stdout).ok()?;
+    Some(translated.trim_end_matches('\n').to_string())
+}
+
+/// Decode the output of `wsl.exe`, which is UTF-16LE unless `WSL_UTF8` is
+/// set.
+pub fn decode_output(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(&[0xff, 0xfe][..]).unwrap_or(bytes);
+    // ASCII text in UTF-16LE has a NUL byte after every character
+    let pairs = bytes.chunks_exact(2);
+    let is_utf16 = pairs.remainder().is_empty() && pairs.clone().any(|pair| pair[1] == 0);
+    if is_utf16 {
+        let units: Vec<u16> = pairs
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
     } else {
-        key.to_owned()
-    };
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
 
-    let wslenv = match env::var("WSLENV") {
-        Ok(original_wslenv) => {
-            // WSLENV exists, add new variable only once
-            let re: regex::Regex =
-                regex::Regex::new(format!(r"(^|:){}(/|:|$)", wslenv_key).as_str())
-                    .expect("Failed to compile regex");
-
-            if original_wslenv.is_empty() {
-                format!("{}", wslenv_key)
-            } else if re.is_match(original_wslenv.as_str()) == false {
-                format!("{}:{}", original_wslenv, wslenv_key)
-            } else {
-                // Don't add anything to WSLENV
-                original_wslenv
+/// Parse the output of `wsl --list --verbose`, e.g.
+///
+/// ```text
+///   NAME            STATE           VERSION
+/// * Ubuntu-20.04    Running         2
+///   Debian          Stopped         1
+/// ```
+///
+/// The header is skipped, since it may be localized. Lines that do not look
+/// like a distribution (e.g. a message that none are installed) are ignored.
+pub fn parse_distributions(output: &str) -> Vec<Distribution> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let line = line.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+            let (is_default, line) = match line.strip_prefix('*') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            // The state may consist of several words
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                return None;
             }
-        }
-        Err(_e) => {
-            // No WSLENV
-            format!("{}", wslenv_key)
+            Some(Distribution {
+                name: fields[0].to_string(),
+                state: fields[1..fields.len() - 1].join(" "),
+                version: fields[fields.len() - 1].parse().ok()?,
+                is_default,
+            })
+        })
+        .collect()
+}
+
+/// Return the installed distributions.
+///
+/// The list is cached, `refresh` forces reading it again from `wsl.exe`.
+/// Returns `None` if it cannot be determined, e.g. if `wsl.exe` does not
+/// support `--verbose`.
+pub fn list_distributions(refresh: bool) -> Option<Vec<Distribution>> {
+    const CACHE_KEY: &str = "distributions";
+    let cached = if refresh {
+        None
+    } else {
+        cache::read(CACHE_KEY, DISTRIBUTIONS_CACHE_TTL)
+    };
+    let output = match cached {
+        Some(output) => output,
+        None => {
+            let output = Command::new("wsl")
+                .args(["--list", "--verbose"])
+                .output()
+                .ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            let output = decode_output(&output.stdout);
+            cache::write(CACHE_KEY, &output);
+            output
         }
     };
+    Some(parse_distributions(&output))
+}
+
+/// Return the name of the WSL default distribution, see
+/// [`list_distributions`].
+pub fn default_distribution() -> Option<String> {
+    list_distributions(false)?
+        .into_iter()
+        .find(|dist| dist.is_default)
+        .map(|dist| dist.name)
+}
+
+/// Return `true` if git can be executed in the distribution `dist`.
+pub fn has_git(dist: &str) -> bool {
+    Command::new("wsl")
+        .arg("--distribution")
+        .arg(dist)
+        .args(["-e", "git", "--version"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Find the first WSL1 distribution in `distributions` for which `has_git`
+/// returns `true`.
+pub fn first_wsl1_with_git(
+    distributions: &[Distribution],
+    has_git: impl Fn(&str) -> bool,
+) -> Option<&Distribution> {
+    distributions
+        .iter()
+        .filter(|dist| dist.version == 1)
+        .find(|dist| has_git(&dist.name))
+}
+
+/// Cached value of [`auto_distribution`] if there is no WSL1 distribution
+/// with git.
+const NO_AUTO_DISTRIBUTION: &str = "none\n";
+
+/// Format the choice of [`auto_distribution`] to be cached.
+fn format_auto_distribution(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("dist {}\n", name),
+        None => NO_AUTO_DISTRIBUTION.to_string(),
+    }
+}
+
+/// Parse a cached choice of [`auto_distribution`].
+///
+/// Returns `None` if `cached` is not a complete value, e.g. because it was cut
+/// off, so that the choice is made again.
+fn parse_auto_distribution(cached: &str) -> Option<Option<String>> {
+    if cached == NO_AUTO_DISTRIBUTION {
+        return Some(None);
+    }
+    let name = cached.strip_prefix("dist ")?.strip_suffix('\n')?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(Some(name.to_string()))
+    }
+}
+
+/// Return the first installed WSL1 distribution that has git, see
+/// [`first_wsl1_with_git`].
+///
+/// The choice is cached as long as the list of distributions, since checking
+/// for git starts each WSL1 distribution.
+pub fn auto_distribution() -> Option<String> {
+    const CACHE_KEY: &str = "auto-distribution";
+    let cached = cache::read(CACHE_KEY, DISTRIBUTIONS_CACHE_TTL)
+        .and_then(|cached| parse_auto_distribution(&cached));
+    if let Some(name) = cached {
+        return name;
+    }
+    let distributions = list_distributions(false)?;
+    let name = first_wsl1_with_git(&distributions, has_git).map(|dist| dist.name.clone());
+    cache::write(CACHE_KEY, &format_auto_distribution(name.as_deref()));
+    name
+}
 
-    env::set_var("WSLENV", wslenv);
+/// Find the distribution called `name`, ignoring case like `wsl.exe` does.
+pub fn find_distribution<'a>(
+    distributions: &'a [Distribution],
+    name: &str,
+) -> Option<&'a Distribution> {
+    distributions
+        .iter()
+        .find(|dist| dist.name.eq_ignore_ascii_case(name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    }
+
+    const SAMPLE_OUTPUT: &str = "  NAME                   STATE           VERSION\r\n\
+                                 * Ubuntu-20.04           Running         2\r\n\
+                                 \x20 Debian                 Stopped         1\r\n\
+                                 \x20 docker-desktop-data    Stopped         2\r\n";
+
+    #[test]
+    fn decode_wsl_output() {
+        assert_eq!(decode_output(&utf16(SAMPLE_OUTPUT)), SAMPLE_OUTPUT);
+        let mut with_bom = vec![0xff, 0xfe];
+        with_bom.extend(utf16("Ubuntu"));
+        assert_eq!(decode_output(&with_bom), "Ubuntu");
+        // Output with WSL_UTF8=1
+        assert_eq!(decode_output(SAMPLE_OUTPUT.as_bytes()), SAMPLE_OUTPUT);
+        assert_eq!(decode_output(&utf16("Ünïcødé")), "Ünïcødé");
+        assert_eq!(decode_output(b""), "");
+    }
+
+    #[test]
+    fn parse_distribution_list() {
+        let dist = |name: &str, state: &str, version: u32, is_default: bool| Distribution {
+            name: name.to_string(),
+            state: state.to_string(),
+            version,
+            is_default,
+        };
+        assert_eq!(
+            parse_distributions(&decode_output(&utf16(SAMPLE_OUTPUT))),
+            vec![
+                dist("Ubuntu-20.04", "Running", 2, true),
+                dist("Debian", "Stopped", 1, false),
+                dist("docker-desktop-data", "Stopped", 2, false),
+            ]
+        );
+
+        // Localized header and states
+        let german = "  NAME      STATUS         VERSION\n* Ubuntu    Wird ausgeführt 2\n  Alpine    Beendet        1\n";
+        assert_eq!(
+            parse_distributions(german),
+            vec![
+                dist("Ubuntu", "Wird ausgeführt", 2, true),
+                dist("Alpine", "Beendet", 1, false)
+            ]
+        );
+
+        let no_distributions = "Windows Subsystem for Linux has no installed distributions.\r\n\
+                                Distributions can be installed by visiting the Microsoft Store:\r\n\
+                                https://aka.ms/wslstore\r\n";
+        assert_eq!(parse_distributions(no_distributions), vec![]);
+        assert_eq!(parse_distributions(""), vec![]);
+    }
+
+    #[test]
+    fn first_wsl1_distribution_with_git() {
+        let distributions = parse_distributions(
+            "NAME STATE VERSION\n\
+             * Ubuntu Running 2\n\
+             \x20 Alpine Stopped 1\n\
+             \x20 Debian Stopped 1\n\
+             \x20 Legacy Stopped 1\n",
+        );
+        let find = |with_git: &[&str]| {
+            first_wsl1_with_git(&distributions, |name| with_git.contains(&name))
+                .map(|dist| dist.name.as_str())
+        };
+        assert_eq!(find(&["Ubuntu", "Debian", "Legacy"]), Some("Debian"));
+        assert_eq!(find(&["Alpine", "Debian"]), Some("Alpine"));
+        assert_eq!(find(&["Ubuntu"]), None);
+        assert_eq!(first_wsl1_with_git(&[], |_| true), None);
+    }
+
+    #[test]
+    fn cache_auto_distribution() {
+        for &name in &[Some("Debian"), Some("My Dist"), None] {
+            assert_eq!(
+                parse_auto_distribution(&format_auto_distribution(name)),
+                Some(name.map(String::from)),
+                "{:?}",
+                name
+            );
+        }
+        // Cut off values are looked up again
+        for &cached in &["", "none", "dist ", "dist \n", "dist Debi", "Debian\n"] {
+            assert_eq!(parse_auto_distribution(cached), None, "{:?}", cached);
+        }
+    }
+
+    #[test]
+    fn find_distribution_by_name() {
+        let distributions = parse_distributions(SAMPLE_OUTPUT);
+        assert_eq!(
+            find_distribution(&distributions, "ubuntu-20.04").map(|d| d.name.as_str()),
+            Some("Ubuntu-20.04")
+        );
+        assert_eq!(
+            find_distribution(&distributions, "DEBIAN").map(|d| d.version),
+            Some(1)
+        );
+        assert_eq!(find_distribution(&distributions, "Ubuntu"), None);
+    }
+
     #[test]
     fn share_variable_to_wsl() {
         // No WSLENV
@@ -75,9 +463,49 @@ mod tests {
         env::set_var("WSLENV", "VAR1:VAR2:VAR3:VAR4:VAR5");
         share_val("VAR5", "5", true);
         assert_eq!("5", env::var("VAR5").unwrap());
-        assert_eq!(
-            "VAR1:VAR2:VAR3:VAR4:VAR5:VAR5/p",
-            env::var("WSLENV").unwrap()
-        );
+        assert_eq!("VAR1:VAR2:VAR3:VAR4:VAR5/p", env::var("WSLENV").unwrap());
+
+        // Variable already in WSLENV with other flags
+        env::set_var("WSLENV", "VAR1/up:VAR2");
+        share_var("VAR1", "l");
+        assert_eq!("VAR1/l:VAR2", env::var("WSLENV").unwrap());
+        env::remove_var("WSLENV");
+    }
+
+    #[test]
+    fn parse_wslenv() {
+        let wslenv = WslEnv::parse("WSLGIT:GIT_DIR/p::TMP/up:GIT_DIR/l:/u");
+        assert_eq!(wslenv.flags("WSLGIT"), Some(""));
+        assert_eq!(wslenv.flags("GIT_DIR"), Some("l"));
+        assert_eq!(wslenv.flags("TMP"), Some("up"));
+        assert_eq!(wslenv.to_string(), "WSLGIT:GIT_DIR/l:TMP/up");
+        // Names differing in case are the same variable
+        assert_eq!(WslEnv::parse("git_dir/p:GIT_DIR").to_string(), "git_dir");
+        assert_eq!(WslEnv::parse(""), WslEnv::default());
+        assert_eq!(WslEnv::parse("").to_string(), "");
+    }
+
+    #[test]
+    fn modify_wslenv() {
+        let mut wslenv = WslEnv::parse("WSLGIT:BASH_ENV/up");
+        assert!(wslenv.contains("BASH_ENV"));
+        assert_eq!(wslenv.flags("BASH_ENV"), Some("up"));
+        assert_eq!(wslenv.flags("WSLGIT"), Some(""));
+        assert_eq!(wslenv.flags("GIT_DIR"), None);
+
+        wslenv.set("GIT_DIR", "p");
+        wslenv.set("WSLGIT", "u");
+        assert_eq!(wslenv.to_string(), "WSLGIT/u:BASH_ENV/up:GIT_DIR/p");
+        wslenv.set("GIT_DIR", "p");
+        assert_eq!(wslenv.to_string(), "WSLGIT/u:BASH_ENV/up:GIT_DIR/p");
+
+        assert_eq!(WslEnv::parse(&wslenv.to_string()), wslenv);
+
+        // Windows environment variable names are case-insensitive
+        let mut wslenv = WslEnv::parse("git_dir/p");
+        assert!(wslenv.contains("GIT_DIR"));
+        assert_eq!(wslenv.flags("Git_Dir"), Some("p"));
+        wslenv.set("GIT_DIR", "l");
+        assert_eq!(wslenv.to_string(), "git_dir/l");
     }
 }
@@ -265,8 +265,8 @@ mod integration {
     fn wslgit_environment_variable() {
         Command::cargo_bin(env!("CARGO_PKG_NAME"))
             .unwrap()
-            // Use pretty format to call 'env'
-            .args(&["log", "-1", "--pretty=format:$(env)"])
+            // Use a shell alias to call 'env'
+            .args(&["-c", "alias.wslgit-env=!env", "wslgit-env"])
             .env("WSLGIT_USE_INTERACTIVE_SHELL", "false")
             .env("WSLENV", "")
             .assert()
@@ -276,8 +276,8 @@ mod integration {
 
         Command::cargo_bin(env!("CARGO_PKG_NAME"))
             .unwrap()
-            // Use pretty format to call 'env'
-            .args(&["log", "-1", "--pretty=format:$(env)"])
+            // Use a shell alias to call 'env'
+            .args(&["-c", "alias.wslgit-env=!env", "wslgit-env"])
             .env("WSLGIT_USE_INTERACTIVE_SHELL", "false")
             .env("WSLENV", "hello")
             .assert()
@@ -290,10 +290,27 @@ mod integration {
     fn shell_environment_variable() {
         Command::cargo_bin(env!("CARGO_PKG_NAME"))
             .unwrap()
-            // Use pretty format to call 'printenv SHELL'
-            .args(&["log", "-1", "--pretty=format:$(printenv SHELL)"])
+            // Use a shell alias to call 'printenv SHELL'
+            .args(&["-c", "alias.wslgit-shell=!printenv SHELL", "wslgit-shell"])
             .assert()
             .success()
             .stdout(predicate::str::contains("/bin/bash"));
     }
+
+    #[test]
+    fn shell_characters_are_not_interpreted() {
+        for interactive in &["false", "true"] {
+            Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .unwrap()
+                .args(&[
+                    "log",
+                    "-n1",
+                    "--pretty=format:$(env) `id` $HOME; a & b 'c' \\d",
+                ])
+                .env("WSLGIT_USE_INTERACTIVE_SHELL", interactive)
+                .assert()
+                .success()
+                .stdout("$(env) `id` $HOME; a & b 'c' \\d");
+        }
+    }
 }